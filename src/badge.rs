@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Coverage badge in the same style as the "flat" badges of shields.io.
+// Refs:
+// - https://shields.io/badges/endpoint-badge
+// - https://github.com/badges/shields/tree/master/badge-maker
+
+use serde_derive::Serialize;
+
+const LABEL: &str = "coverage";
+
+/// Named colors of shields.io and their hex codes.
+const NAMED_COLORS: &[(&str, &str)] = &[
+    ("brightgreen", "#4c1"),
+    ("green", "#97ca00"),
+    ("yellowgreen", "#a4a61d"),
+    ("yellow", "#dfb317"),
+    ("orange", "#fe7d37"),
+    ("red", "#e05d44"),
+    ("blue", "#007ec6"),
+    ("lightgrey", "#9f9f9f"),
+];
+
+/// Returns the hex code (e.g., `#4c1`) of the given named color or hex code with or without
+/// the leading `#`, or `None` if it is not a valid color.
+pub(crate) fn color_hex(color: &str) -> Option<String> {
+    if let Some(&(_, hex)) = NAMED_COLORS.iter().find(|&&(name, _)| name == color) {
+        return Some(hex.to_owned());
+    }
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    (matches!(hex.len(), 3 | 6) && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+        .then(|| format!("#{hex}"))
+}
+
+/// Returns the badge in SVG format.
+pub(crate) fn to_svg(percent: f64, color: &str) -> String {
+    let message = format!("{percent:.2}%");
+    let color = color_hex(color).unwrap_or_else(|| "#e05d44".to_owned());
+    let label_width = text_width(LABEL) + 10;
+    let message_width = text_width(&message) + 10;
+    let width = label_width + message_width;
+    // Texts are rendered at 10x scale for better kerning.
+    let label_x = label_width * 5;
+    let message_x = label_width * 10 + message_width * 5;
+    let label_length = (label_width - 10) * 10;
+    let message_length = (message_width - 10) * 10;
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"20\" role=\"img\" \
+         aria-label=\"{LABEL}: {message}\">\
+         <title>{LABEL}: {message}</title>\
+         <linearGradient id=\"s\" x2=\"0\" y2=\"100%\">\
+         <stop offset=\"0\" stop-color=\"#bbb\" stop-opacity=\".1\"/>\
+         <stop offset=\"1\" stop-opacity=\".1\"/>\
+         </linearGradient>\
+         <clipPath id=\"r\"><rect width=\"{width}\" height=\"20\" rx=\"3\" fill=\"#fff\"/></clipPath>\
+         <g clip-path=\"url(#r)\">\
+         <rect width=\"{label_width}\" height=\"20\" fill=\"#555\"/>\
+         <rect x=\"{label_width}\" width=\"{message_width}\" height=\"20\" fill=\"{color}\"/>\
+         <rect width=\"{width}\" height=\"20\" fill=\"url(#s)\"/>\
+         </g>\
+         <g fill=\"#fff\" text-anchor=\"middle\" \
+         font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\" font-size=\"110\">\
+         <text x=\"{label_x}\" y=\"150\" fill=\"#010101\" fill-opacity=\".3\" \
+         transform=\"scale(.1)\" textLength=\"{label_length}\">{LABEL}</text>\
+         <text x=\"{label_x}\" y=\"140\" transform=\"scale(.1)\" \
+         textLength=\"{label_length}\">{LABEL}</text>\
+         <text x=\"{message_x}\" y=\"150\" fill=\"#010101\" fill-opacity=\".3\" \
+         transform=\"scale(.1)\" textLength=\"{message_length}\">{message}</text>\
+         <text x=\"{message_x}\" y=\"140\" transform=\"scale(.1)\" \
+         textLength=\"{message_length}\">{message}</text>\
+         </g>\
+         </svg>\n"
+    )
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Endpoint<'a> {
+    schema_version: u32,
+    label: &'a str,
+    message: String,
+    color: &'a str,
+}
+
+/// Returns the badge in shields.io endpoint JSON format.
+pub(crate) fn to_shields_json(percent: f64, color: &str) -> String {
+    let endpoint = Endpoint {
+        schema_version: 1,
+        label: LABEL,
+        message: format!("{percent:.2}%"),
+        // shields.io accepts hex codes without the leading `#`.
+        color: color.strip_prefix('#').unwrap_or(color),
+    };
+    serde_json::to_string(&endpoint).unwrap()
+}
+
+/// Returns the approximate width of the given text in 11px Verdana.
+fn text_width(s: &str) -> u64 {
+    s.chars()
+        .map(|c| match c {
+            'i' | 'l' | 'j' | '.' | ',' | ':' | ' ' => 4,
+            'f' | 'r' | 't' => 5,
+            'm' | 'w' | '%' => 10,
+            'A'..='Z' => 8,
+            _ => 7,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn color_hex() {
+        assert_eq!(super::color_hex("brightgreen").as_deref(), Some("#4c1"));
+        assert_eq!(super::color_hex("#00FF00").as_deref(), Some("#00FF00"));
+        assert_eq!(super::color_hex("abc").as_deref(), Some("#abc"));
+        assert_eq!(super::color_hex("pink"), None);
+        assert_eq!(super::color_hex("#abcd"), None);
+    }
+
+    #[test]
+    fn badge() {
+        assert_eq!(
+            super::to_shields_json(85.714_285, "#4c1"),
+            r#"{"schemaVersion":1,"label":"coverage","message":"85.71%","color":"4c1"}"#
+        );
+        let svg = super::to_svg(85.714_285, "yellow");
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"116\" "));
+        assert!(svg.contains("<title>coverage: 85.71%</title>"));
+        assert!(svg.contains("<rect x=\"64\" width=\"52\" height=\"20\" fill=\"#dfb317\"/>"));
+    }
+}
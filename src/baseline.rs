@@ -0,0 +1,159 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Coverage baseline for --baseline and --save-baseline.
+
+use std::{collections::BTreeMap, iter};
+
+use anyhow::{Context as _, Result};
+use camino::Utf8Path;
+use cargo_llvm_cov::json::{CoverageKind, LlvmCovJsonExport, Summary};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::fs;
+
+/// Kinds of coverage compared with the baseline.
+const KINDS: [CoverageKind; 3] =
+    [CoverageKind::Functions, CoverageKind::Lines, CoverageKind::Regions];
+
+/// The total coverage and coverage of each file.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Baseline {
+    totals: Summary,
+    /// File name relative to the workspace root -> coverage of the file.
+    files: BTreeMap<String, Summary>,
+}
+
+/// Coverage that has decreased from the baseline.
+pub(crate) struct Regression<'a> {
+    /// `None` for the total coverage.
+    pub(crate) file: Option<&'a str>,
+    pub(crate) kind: CoverageKind,
+    pub(crate) baseline: f64,
+    pub(crate) current: f64,
+}
+
+impl Baseline {
+    pub(crate) fn new(json: &LlvmCovJsonExport, workspace_root: &Utf8Path) -> Self {
+        let mut totals = Summary::default();
+        let mut files = BTreeMap::new();
+        for file in json.data.iter().flat_map(|data| &data.files) {
+            // File names are absolute unless --remap-path-prefix is used. Store them relative to
+            // the workspace root so that the baseline can be shared across machines.
+            let name = Utf8Path::new(&file.filename)
+                .strip_prefix(workspace_root)
+                .map_or(&*file.filename, Utf8Path::as_str);
+            totals.add(&file.summary);
+            files.insert(name.to_owned(), file.summary.clone());
+        }
+        Self { totals, files }
+    }
+
+    pub(crate) fn load(path: &Utf8Path) -> Result<Self> {
+        serde_json::from_str(&fs::read_to_string(path)?)
+            .with_context(|| format!("failed to parse baseline {path}"))
+    }
+
+    pub(crate) fn save(&self, path: &Utf8Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut out = serde_json::to_string_pretty(self)?;
+        out.push('\n');
+        fs::write(path, out)?;
+        Ok(())
+    }
+
+    /// Returns the total coverage.
+    pub(crate) fn totals(&self) -> &Summary {
+        &self.totals
+    }
+
+    /// Returns the coverage of each file (file name relative to the workspace root -> coverage).
+    pub(crate) fn files(&self) -> &BTreeMap<String, Summary> {
+        &self.files
+    }
+
+    /// Returns the coverage that has decreased from `self` by more than `tolerance` percentage
+    /// points.
+    ///
+    /// Files that exist only in one of `self` and `current` are ignored.
+    pub(crate) fn regressions<'a>(&'a self, current: &Self, tolerance: f64) -> Vec<Regression<'a>> {
+        let files = self.files.iter().filter_map(|(name, baseline)| {
+            Some((Some(&**name), baseline, current.files.get(name)?))
+        });
+        let mut regressions = vec![];
+        for (file, baseline, current) in
+            iter::once((None, &self.totals, &current.totals)).chain(files)
+        {
+            for kind in KINDS {
+                let (baseline, current) = (baseline.counts(kind), current.counts(kind));
+                // Coverage is meaningless if there is nothing to cover.
+                if baseline.count == 0 || current.count == 0 {
+                    continue;
+                }
+                if baseline.percent - current.percent > tolerance {
+                    regressions.push(Regression {
+                        file,
+                        kind,
+                        baseline: baseline.percent,
+                        current: current.percent,
+                    });
+                }
+            }
+        }
+        regressions
+    }
+
+    /// Returns `true` if any kind of the total coverage has increased from `self`.
+    pub(crate) fn improved(&self, current: &Self) -> bool {
+        KINDS
+            .into_iter()
+            .any(|kind| current.totals.counts(kind).percent > self.totals.counts(kind).percent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cargo_llvm_cov::json::{CoverageKind, LlvmCovJsonExport, Summary};
+
+    use super::Baseline;
+    use crate::fs;
+
+    #[test]
+    fn regressions() {
+        let model = format!(
+            "{}/tests/fixtures/coverage-reports/real1/all.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let json: LlvmCovJsonExport =
+            serde_json::from_str(&fs::read_to_string(model).unwrap()).unwrap();
+        let baseline = Baseline::new(&json, "/ws".into());
+        assert_eq!(baseline.files.len(), 3);
+        assert_eq!(baseline.totals.lines.count, 27);
+        assert!(baseline.regressions(&baseline, 0_f64).is_empty());
+        assert!(!baseline.improved(&baseline));
+
+        let mut current =
+            serde_json::from_value::<Baseline>(serde_json::to_value(&baseline).unwrap()).unwrap();
+        let file = current.files.get_mut("src/lib.rs").unwrap();
+        file.lines.covered = 10; // 84.62% -> 76.92%
+        file.lines.percent = 10_f64 / 13_f64 * 100_f64;
+        current.totals.lines.covered = 18; // 70.37% -> 66.67%
+        current.totals.lines.percent = 18_f64 / 27_f64 * 100_f64;
+        // Files that don't exist in the baseline are ignored.
+        current.files.insert("src/new.rs".to_owned(), Summary::default());
+
+        let regressions = baseline.regressions(&current, 0_f64);
+        assert_eq!(regressions.len(), 2);
+        assert_eq!(regressions[0].file, None);
+        assert_eq!(regressions[1].file, Some("src/lib.rs"));
+        assert!(regressions.iter().all(|r| matches!(r.kind, CoverageKind::Lines)));
+        let regressions = baseline.regressions(&current, 5_f64);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].file, Some("src/lib.rs"));
+        assert!(baseline.regressions(&current, 10_f64).is_empty());
+
+        assert!(current.improved(&baseline));
+        assert!(!baseline.improved(&current));
+    }
+}
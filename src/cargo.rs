@@ -25,6 +25,8 @@ pub(crate) struct Workspace {
     pub(crate) build_dir: Option<Utf8PathBuf>,
     pub(crate) default_output_dir: Utf8PathBuf,
     pub(crate) doctests_dir: Utf8PathBuf,
+    /// Directory to write profraw files of each test into. (--per-test)
+    pub(crate) per_test_dir: Utf8PathBuf,
     pub(crate) profdata_file: Utf8PathBuf,
 
     rustc: ProcessBuilder,
@@ -94,6 +96,7 @@ impl Workspace {
         let build_dir = config.build.build_dir.as_ref().and(Some(build_dir));
         let default_output_dir = metadata.target_directory.join("llvm-cov");
         let doctests_dir = target_dir.join("doctestbins");
+        let per_test_dir = target_dir.join("per-test");
 
         let name = metadata.workspace_root.file_name().unwrap_or("default").to_owned();
         let profdata_file = target_dir.join(format!("{name}.profdata"));
@@ -108,6 +111,7 @@ impl Workspace {
             build_dir,
             default_output_dir,
             doctests_dir,
+            per_test_dir,
             profdata_file,
             rustc,
             target_for_config,
@@ -20,9 +20,9 @@ use crate::{
     term,
 };
 
-pub(crate) fn run((mut args, unresolved_args): (Args, UnresolvedArgs)) -> Result<()> {
+pub(crate) fn run((mut args, mut unresolved_args): (Args, UnresolvedArgs)) -> Result<()> {
     let mut ws = Workspace::new(unresolved_args.manifest_path.as_deref(), None, false)?;
-    cli::merge_config_and_args(&mut ws, &mut None, &mut args.verbose, unresolved_args.color)?;
+    cli::merge_config_and_args(&mut ws, &mut args, &mut unresolved_args)?;
     drop(unresolved_args);
     term::set_coloring(&mut ws.config.term.color);
 
@@ -134,7 +134,7 @@ fn clean_ws_inner(
         return Ok(());
     }
 
-    for format in &["html", "text"] {
+    for format in &["html", "text", "gcov"] {
         rm_rf(ws.default_output_dir.join(format), verbose)?;
     }
 
@@ -154,6 +154,7 @@ fn clean_profraw_files(ws: &Workspace, verbose: bool) -> Result<()> {
     {
         rm_rf(path, verbose)?;
     }
+    rm_rf(&ws.per_test_dir, verbose)?;
     Ok(())
 }
 
@@ -2,15 +2,22 @@
 
 use std::{ffi::OsString, io, mem, str::FromStr};
 
-use anyhow::{Error, Result, bail, format_err};
+use anyhow::{Context as _, Error, Result, bail, format_err};
 use camino::{Utf8Path, Utf8PathBuf};
 use cargo_config2::Color;
+use cargo_llvm_cov::json::CoverageKind;
 use lexopt::{
     Arg::{Long, Short, Value},
     ValueExt as _,
 };
 
-use crate::{env, process::ProcessBuilder, term};
+use crate::{
+    badge,
+    config::{LlvmCovConfig, ReportOutput},
+    env,
+    process::ProcessBuilder,
+    term,
+};
 
 // TODO: add --config option and passthrough to cargo-config: https://github.com/rust-lang/cargo/pull/10755/
 
@@ -28,6 +35,8 @@ pub(crate) struct Args {
     pub(crate) clean: CleanOptions,
     /// Options only referred in "show-env" operations. (show-env subcommand)
     pub(crate) show_env: ShowEnvOptions,
+    /// Options only referred in "select" operations. (select subcommand)
+    pub(crate) select: SelectOptions,
 
     // -------------------------------------------------------------------------
     // Options referred by various operations
@@ -78,6 +87,11 @@ pub(crate) struct Args {
     /// Show coverage of the specified dependency instead of the crates in the current workspace.
     pub(crate) dep_coverage: Vec<String>,
 
+    /// Collect coverage of each test separately and report which tests executed each line.
+    ///
+    /// This is only supported for `nextest` and `report` subcommands.
+    pub(crate) per_test: bool,
+
     pub(crate) nextest_archive_file: Option<String>,
 }
 
@@ -106,6 +120,9 @@ pub(crate) enum Subcommand {
 
     /// Build and archive tests with cargo nextest
     NextestArchive,
+
+    /// Select tests affected by the diff based on per-test coverage
+    Select,
 }
 
 static CARGO_LLVM_COV_USAGE: &str = include_str!("../docs/cargo-llvm-cov.txt");
@@ -117,6 +134,7 @@ static CARGO_LLVM_COV_SHOW_ENV_USAGE: &str = include_str!("../docs/cargo-llvm-co
 static CARGO_LLVM_COV_NEXTEST_USAGE: &str = include_str!("../docs/cargo-llvm-cov-nextest.txt");
 static CARGO_LLVM_COV_NEXTEST_ARCHIVE_USAGE: &str =
     include_str!("../docs/cargo-llvm-cov-nextest-archive.txt");
+static CARGO_LLVM_COV_SELECT_USAGE: &str = include_str!("../docs/cargo-llvm-cov-select.txt");
 
 impl Subcommand {
     fn can_passthrough(subcommand: Self) -> bool {
@@ -133,6 +151,7 @@ impl Subcommand {
             Self::ShowEnv => CARGO_LLVM_COV_SHOW_ENV_USAGE,
             Self::Nextest { .. } => CARGO_LLVM_COV_NEXTEST_USAGE,
             Self::NextestArchive => CARGO_LLVM_COV_NEXTEST_ARCHIVE_USAGE,
+            Self::Select => CARGO_LLVM_COV_SELECT_USAGE,
         }
     }
 
@@ -146,6 +165,7 @@ impl Subcommand {
             Self::ShowEnv => "show-env",
             Self::Nextest { .. } => "nextest",
             Self::NextestArchive => "nextest-archive",
+            Self::Select => "select",
         }
     }
 
@@ -172,6 +192,7 @@ impl FromStr for Subcommand {
             "show-env" => Ok(Self::ShowEnv),
             "nextest" => Ok(Self::Nextest { archive_file: false }),
             "nextest-archive" => Ok(Self::NextestArchive),
+            "select" => Ok(Self::Select),
             _ => bail!("unrecognized subcommand {s}"),
         }
     }
@@ -250,49 +271,200 @@ pub(crate) struct ReportOptions {
 
     /// Export coverage data in "json" format
     ///
-    /// If --output-path is not specified, the report will be printed to stdout.
+    /// If PATH is not specified, the report will be written to the file specified by
+    /// --output-path, or printed to stdout if --output-path is also not specified.
     ///
     /// This internally calls `llvm-cov export -format=text`.
     /// See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export> for more.
     pub(crate) json: bool,
+    /// Path specified by `--json=<PATH>`.
+    pub(crate) json_output_path: Option<Utf8PathBuf>,
     /// Export coverage data in "lcov" format
     ///
-    /// If --output-path is not specified, the report will be printed to stdout.
+    /// If PATH is not specified, the report will be written to the file specified by
+    /// --output-path, or printed to stdout if --output-path is also not specified.
     ///
     /// This internally calls `llvm-cov export -format=lcov`.
     /// See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export> for more.
     pub(crate) lcov: bool,
+    /// Path specified by `--lcov=<PATH>`.
+    pub(crate) lcov_output_path: Option<Utf8PathBuf>,
 
     /// Export coverage data in "cobertura" XML format
     ///
-    /// If --output-path is not specified, the report will be printed to stdout.
+    /// If PATH is not specified, the report will be written to the file specified by
+    /// --output-path, or printed to stdout if --output-path is also not specified.
     ///
-    /// This internally calls `llvm-cov export -format=lcov` and then converts to cobertura.xml.
+    /// This internally calls `llvm-cov export -format=text` and then converts to cobertura.xml.
     /// See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export> for more.
     pub(crate) cobertura: bool,
+    /// Path specified by `--cobertura=<PATH>`.
+    pub(crate) cobertura_output_path: Option<Utf8PathBuf>,
 
     /// Export coverage data in "Codecov Custom Coverage" format
     ///
-    /// If --output-path is not specified, the report will be printed to stdout.
+    /// If PATH is not specified, the report will be written to the file specified by
+    /// --output-path, or printed to stdout if --output-path is also not specified.
     ///
-    /// This internally calls `llvm-cov export -format=json` and then converts to codecov.json.
+    /// This internally calls `llvm-cov export -format=text` and then converts to codecov.json.
     /// See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export> for more.
     pub(crate) codecov: bool,
+    /// Path specified by `--codecov=<PATH>`.
+    pub(crate) codecov_output_path: Option<Utf8PathBuf>,
+
+    /// Export coverage data in SonarQube "generic test coverage" XML format
+    ///
+    /// If PATH is not specified, the report will be written to the file specified by
+    /// --output-path, or printed to stdout if --output-path is also not specified.
+    ///
+    /// This internally calls `llvm-cov export -format=text` and then converts to sonarqube.xml.
+    /// See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export> for more.
+    pub(crate) sonarqube: bool,
+    /// Path specified by `--sonarqube=<PATH>`.
+    pub(crate) sonarqube_output_path: Option<Utf8PathBuf>,
+
+    /// Export coverage data in Coveralls API job JSON format
+    ///
+    /// If PATH is not specified, the report will be written to the file specified by
+    /// --output-path, or printed to stdout if --output-path is also not specified.
+    ///
+    /// This internally calls `llvm-cov export -format=text` and then converts to coveralls.json.
+    /// See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export> for more.
+    pub(crate) coveralls: bool,
+    /// Path specified by `--coveralls=<PATH>`.
+    pub(crate) coveralls_output_path: Option<Utf8PathBuf>,
+
+    /// Export coverage data in JaCoCo XML format
+    ///
+    /// If PATH is not specified, the report will be written to the file specified by
+    /// --output-path, or printed to stdout if --output-path is also not specified.
+    ///
+    /// This internally calls `llvm-cov export -format=text` and then converts to jacoco.xml.
+    /// See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export> for more.
+    pub(crate) jacoco: bool,
+    /// Path specified by `--jacoco=<PATH>`.
+    pub(crate) jacoco_output_path: Option<Utf8PathBuf>,
+
+    /// Export coverage summary in Markdown format
+    ///
+    /// If PATH is not specified, the report will be written to the file specified by
+    /// --output-path, or printed to stdout if --output-path is also not specified.
+    ///
+    /// The summary contains the total and per-package coverage and the files with the lowest line
+    /// coverage, and the changes from the baseline if --baseline is specified. If the
+    /// `GITHUB_STEP_SUMMARY` environment variable is set, the summary is also appended to that file.
+    ///
+    /// This internally calls `llvm-cov export -format=text -summary-only` and then converts to a
+    /// Markdown summary.
+    pub(crate) markdown: bool,
+    /// Path specified by `--markdown=<PATH>`.
+    pub(crate) markdown_output_path: Option<Utf8PathBuf>,
+
+    /// Export uncovered functions, regions, and branches in SARIF format
+    ///
+    /// If PATH is not specified, the report will be written to the file specified by
+    /// --output-path, or printed to stdout if --output-path is also not specified.
+    ///
+    /// Each uncovered function, and each uncovered region and partially covered branch in covered
+    /// functions is reported as a result with its location. See also --sarif-level.
+    ///
+    /// This internally calls `llvm-cov export -format=text` and then converts to SARIF 2.1.0
+    /// log. See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export> for more.
+    pub(crate) sarif: bool,
+    /// Path specified by `--sarif=<PATH>`.
+    pub(crate) sarif_output_path: Option<Utf8PathBuf>,
+    /// Severity of each kind of results in the SARIF report.
+    pub(crate) sarif_level: Option<SarifLevels>,
+
+    /// Export coverage of each file in CSV format
+    ///
+    /// If PATH is not specified, the report will be written to the file specified by
+    /// --output-path, or printed to stdout if --output-path is also not specified.
+    ///
+    /// Each row contains the package, the path, and the number of items, the number of covered
+    /// items, and the percentage of covered items for lines, regions, functions, branches, and
+    /// instantiations. See also --csv-functions.
+    ///
+    /// This internally calls `llvm-cov export -format=text` and then converts to CSV.
+    /// See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export> for more.
+    pub(crate) csv: bool,
+    /// Path specified by `--csv=<PATH>`.
+    pub(crate) csv_output_path: Option<Utf8PathBuf>,
+    /// Export coverage of each function instead of each file in the CSV format.
+    pub(crate) csv_functions: bool,
+
+    /// Export coverage data in Istanbul JSON format
+    ///
+    /// If PATH is not specified, the report will be written to the file specified by
+    /// --output-path, or printed to stdout if --output-path is also not specified.
+    ///
+    /// The report is in the same format as `coverage-final.json` written by Istanbul and nyc,
+    /// so it can be merged with coverage of JavaScript and TypeScript code by `nyc merge` and
+    /// rendered by Istanbul reporters. Regions, functions, and branches are reported as
+    /// statements, functions, and branches respectively.
+    ///
+    /// This internally calls `llvm-cov export -format=text` and then converts to Istanbul JSON.
+    /// See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export> for more.
+    pub(crate) istanbul: bool,
+    /// Path specified by `--istanbul=<PATH>`.
+    pub(crate) istanbul_output_path: Option<Utf8PathBuf>,
+
+    /// Export coverage metrics in OpenMetrics text format
+    ///
+    /// If PATH is not specified, the report will be written to the file specified by
+    /// --output-path, or printed to stdout if --output-path is also not specified.
+    ///
+    /// The number of instrumented items, the number of covered items, and the ratio of covered
+    /// items are written as gauges for each kind of coverage (e.g.,
+    /// `cargo_llvm_cov_lines_covered`). The total coverage has no labels, the coverage of each
+    /// package has the `package` label, and the coverage of each file has the `package` and
+    /// `file` labels. The report can be collected by the textfile collector of Prometheus
+    /// node_exporter (the file name must end with `.prom`).
+    ///
+    /// This internally calls `llvm-cov export -format=text` and then converts to OpenMetrics
+    /// text format. See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export> for
+    /// more.
+    pub(crate) openmetrics: bool,
+    /// Path specified by `--openmetrics=<PATH>`.
+    pub(crate) openmetrics_output_path: Option<Utf8PathBuf>,
+
+    /// Export annotated source files in gcov text format
+    ///
+    /// If DIR is not specified, the report will be generated in `gcov` directory in the directory
+    /// specified by --output-dir (default to `target/llvm-cov/gcov`).
+    ///
+    /// A `.gcov` file is written for each source file, and its name is the path of the source
+    /// file relative to the workspace root with `/` replaced by `#` (e.g., `src#lib.rs.gcov`),
+    /// the same as `gcov --preserve-paths`. These files can be consumed by gcovr
+    /// (`gcovr --use-gcov-files`) and other tools that support gcov text format.
+    ///
+    /// This internally calls `llvm-cov export -format=text` and then converts to gcov text
+    /// format. See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export> for more.
+    pub(crate) gcov: bool,
+    /// Directory specified by `--gcov=<DIR>`.
+    pub(crate) gcov_output_dir: Option<Utf8PathBuf>,
 
     /// Generate coverage report in "text" format
     ///
-    /// If --output-path or --output-dir is not specified, the report will be printed to stdout.
+    /// If PATH is not specified, the report will be written to the file specified by
+    /// --output-path or the directory specified by --output-dir, or printed to stdout if
+    /// neither of them is specified.
     ///
     /// This internally calls `llvm-cov show -format=text`.
     /// See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-show> for more.
     pub(crate) text: bool,
+    /// Path specified by `--text=<PATH>`.
+    pub(crate) text_output_path: Option<Utf8PathBuf>,
     /// Generate coverage report in "html" format
     ///
-    /// If --output-dir is not specified, the report will be generated in `target/llvm-cov/html` directory.
+    /// If DIR is not specified, the report will be generated in `html` directory in the directory
+    /// specified by --output-dir (default to `target/llvm-cov/html`).
     ///
     /// This internally calls `llvm-cov show -format=html`.
     /// See <https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-show> for more.
     pub(crate) html: bool,
+    /// Directory specified by `--html=<DIR>`.
+    pub(crate) html_output_dir: Option<Utf8PathBuf>,
     /// Generate coverage reports in "html" format and open them in a browser after the operation.
     ///
     /// See --html for more.
@@ -308,10 +480,13 @@ pub(crate) struct ReportOptions {
     ///
     /// This flag can only be used together with --json, --lcov, --cobertura, --codecov, or --text.
     /// See --output-dir for --html and --open.
+    ///
+    /// When multiple report formats are specified, use `--<FORMAT>=<PATH>` (e.g.,
+    /// `--lcov=lcov.info --cobertura=cobertura.xml`) to specify the path for each format instead.
     pub(crate) output_path: Option<Utf8PathBuf>,
     /// Specify a directory to write coverage report into (default to `target/llvm-cov`).
     ///
-    /// This flag can only be used together with --text, --html, or --open.
+    /// This flag can only be used together with --text, --html, --open, or --gcov.
     /// See also --output-path.
     // If the format flag is not specified, this flag is no-op.
     pub(crate) output_dir: Option<Utf8PathBuf>,
@@ -325,13 +500,13 @@ pub(crate) struct ReportOptions {
     /// Show instantiations in report
     pub(crate) show_instantiations: bool,
     /// Exit with a status of 1 if the total function coverage is less than MIN percent.
-    pub(crate) fail_under_functions: Option<f64>,
+    pub(crate) fail_under_functions: Option<Threshold>,
     /// Exit with a status of 1 if the total line coverage is less than MIN percent.
-    pub(crate) fail_under_lines: Option<f64>,
+    pub(crate) fail_under_lines: Option<Threshold>,
     /// Exit with a status of 1 if the files line coverage is less than MIN percent.
     pub(crate) fail_under_file_lines: Option<f64>,
     /// Exit with a status of 1 if the total region coverage is less than MIN percent.
-    pub(crate) fail_under_regions: Option<f64>,
+    pub(crate) fail_under_regions: Option<Threshold>,
     /// Exit with a status of 1 if the uncovered lines are greater than MAX.
     pub(crate) fail_uncovered_lines: Option<u64>,
     /// Exit with a status of 1 if the uncovered regions are greater than MAX.
@@ -340,8 +515,41 @@ pub(crate) struct ReportOptions {
     pub(crate) fail_uncovered_functions: Option<u64>,
     /// Show lines with no coverage.
     pub(crate) show_missing_lines: bool,
+    /// Print lines with no coverage as annotations of the given CI system or editor.
+    pub(crate) annotations: Option<Annotations>,
+    /// Print coverage summary as TeamCity service messages.
+    pub(crate) teamcity: bool,
+    /// Show coverage summary for each package.
+    pub(crate) package_summary: bool,
+    /// Compute coverage of lines added or modified relative to the given git revision.
+    pub(crate) diff_base: Option<String>,
+    /// Compute coverage of lines added or modified by the given unified diff file.
+    pub(crate) diff_file: Option<Utf8PathBuf>,
+    /// Exit with a status of 1 if the line coverage of lines added or modified by the diff is
+    /// less than MIN percent.
+    pub(crate) fail_under_diff_lines: Option<f64>,
+    /// Exit with a status of 1 if the coverage decreased from the baseline stored in the given
+    /// file.
+    pub(crate) baseline: Option<Utf8PathBuf>,
+    /// Allow the coverage to decrease from the baseline by up to the given percentage points.
+    pub(crate) baseline_tolerance: Option<f64>,
+    /// Update the baseline if no coverage decreased and the total coverage increased.
+    pub(crate) update_baseline: bool,
+    /// Save the total coverage and coverage of each file to the given file as a baseline.
+    pub(crate) save_baseline: Option<Utf8PathBuf>,
+    /// Write a coverage badge in SVG format to the given file.
+    pub(crate) badge: Option<Utf8PathBuf>,
+    /// Write a coverage badge in shields.io endpoint JSON format to the given file.
+    pub(crate) shields_json: Option<Utf8PathBuf>,
+    /// Coverage shown in the badge (default to lines).
+    pub(crate) badge_metric: Option<CoverageKind>,
+    /// Colors of the badge for each minimum coverage.
+    pub(crate) badge_colors: Option<BadgeColors>,
     /// Include build script in coverage report.
     pub(crate) include_build_script: bool,
+    /// Include source files of the measured packages that are not in the coverage data as
+    /// uncovered files.
+    pub(crate) include_all_sources: bool,
     /// Skip functions in coverage report.
     pub(crate) skip_functions: bool,
 }
@@ -355,7 +563,10 @@ impl ReportOptions {
         // Handle options specific to certain subcommands.
         let (subcommands_without_report, no_report_incompat) = match subcommand {
             // subcommands without generate_report in main.rs.
-            Subcommand::Clean | Subcommand::ShowEnv | Subcommand::NextestArchive => (true, true),
+            Subcommand::Clean
+            | Subcommand::ShowEnv
+            | Subcommand::NextestArchive
+            | Subcommand::Select => (true, true),
             Subcommand::Report { .. } => (false, true),
             Subcommand::None | Subcommand::Test | Subcommand::Run | Subcommand::Nextest { .. } => {
                 (false, false)
@@ -372,11 +583,37 @@ impl ReportOptions {
             let Self {
                 no_report: _,
                 json,
+                json_output_path: _,
                 lcov,
+                lcov_output_path: _,
                 cobertura,
+                cobertura_output_path: _,
                 codecov,
+                codecov_output_path: _,
+                sonarqube,
+                sonarqube_output_path: _,
+                coveralls,
+                coveralls_output_path: _,
+                jacoco,
+                jacoco_output_path: _,
+                markdown,
+                markdown_output_path: _,
+                sarif,
+                sarif_output_path: _,
+                csv,
+                csv_output_path: _,
+                csv_functions,
+                istanbul,
+                istanbul_output_path: _,
+                openmetrics,
+                openmetrics_output_path: _,
+                gcov,
+                gcov_output_dir: _,
+                sarif_level,
                 text,
+                text_output_path: _,
                 html,
+                html_output_dir: _,
                 open,
                 summary_only,
                 output_path,
@@ -393,7 +630,22 @@ impl ReportOptions {
                 fail_uncovered_regions,
                 fail_uncovered_functions,
                 show_missing_lines,
+                annotations,
+                teamcity,
+                package_summary,
+                diff_base,
+                diff_file,
+                fail_under_diff_lines,
+                baseline,
+                baseline_tolerance,
+                update_baseline,
+                save_baseline,
+                badge,
+                shields_json,
+                badge_metric,
+                badge_colors,
                 include_build_script,
+                include_all_sources,
                 skip_functions,
             } = self;
             for (flag, passed) in [
@@ -401,6 +653,17 @@ impl ReportOptions {
                 ("--lcov", *lcov),
                 ("--cobertura", *cobertura),
                 ("--codecov", *codecov),
+                ("--sonarqube", *sonarqube),
+                ("--coveralls", *coveralls),
+                ("--jacoco", *jacoco),
+                ("--markdown", *markdown),
+                ("--sarif", *sarif),
+                ("--csv", *csv),
+                ("--csv-functions", *csv_functions),
+                ("--istanbul", *istanbul),
+                ("--openmetrics", *openmetrics),
+                ("--gcov", *gcov),
+                ("--sarif-level", sarif_level.is_some()),
                 ("--text", *text),
                 ("--html", *html),
                 ("--open", *open),
@@ -419,7 +682,23 @@ impl ReportOptions {
                 ("--fail-uncovered-regions", fail_uncovered_regions.is_some()),
                 ("--fail-uncovered-functions", fail_uncovered_functions.is_some()),
                 ("--show-missing-lines", *show_missing_lines),
+                ("--annotations", annotations.is_some()),
+                ("--teamcity", *teamcity),
+                ("--package-summary", *package_summary),
+                // select subcommand selects tests based on the diff.
+                ("--diff-base", diff_base.is_some() && subcommand != Subcommand::Select),
+                ("--diff-file", diff_file.is_some() && subcommand != Subcommand::Select),
+                ("--fail-under-diff-lines", fail_under_diff_lines.is_some()),
+                ("--baseline", baseline.is_some()),
+                ("--baseline-tolerance", baseline_tolerance.is_some()),
+                ("--update-baseline", *update_baseline),
+                ("--save-baseline", save_baseline.is_some()),
+                ("--badge", badge.is_some()),
+                ("--shields-json", shields_json.is_some()),
+                ("--badge-metric", badge_metric.is_some()),
+                ("--badge-colors", badge_colors.is_some()),
                 ("--include-build-script", *include_build_script),
+                ("--include-all-sources", *include_all_sources),
                 ("--skip-functions", *skip_functions),
             ] {
                 if passed {
@@ -435,105 +714,340 @@ impl ReportOptions {
         }
 
         // conflicts
-        // TODO: handle these mutual exclusions elegantly.
-        if self.lcov {
-            let flag = "--lcov";
-            if self.json {
-                conflicts(flag, "--json")?;
-            }
-        }
-        if self.cobertura {
-            let flag = "--cobertura";
-            if self.json {
-                conflicts(flag, "--json")?;
-            }
-            if self.lcov {
-                conflicts(flag, "--lcov")?;
+        // Multiple report formats can be specified at once, but each report must be written
+        // to a different destination.
+        let mut default_destination = vec![];
+        for (flag, passed, path) in [
+            ("--json", self.json, &self.json_output_path),
+            ("--lcov", self.lcov, &self.lcov_output_path),
+            ("--cobertura", self.cobertura, &self.cobertura_output_path),
+            ("--codecov", self.codecov, &self.codecov_output_path),
+            ("--sonarqube", self.sonarqube, &self.sonarqube_output_path),
+            ("--coveralls", self.coveralls, &self.coveralls_output_path),
+            ("--jacoco", self.jacoco, &self.jacoco_output_path),
+            ("--markdown", self.markdown, &self.markdown_output_path),
+            ("--sarif", self.sarif, &self.sarif_output_path),
+            ("--csv", self.csv, &self.csv_output_path),
+            ("--istanbul", self.istanbul, &self.istanbul_output_path),
+            ("--openmetrics", self.openmetrics, &self.openmetrics_output_path),
+            ("--text", self.text, &self.text_output_path),
+        ] {
+            if !passed || path.is_some() {
+                continue;
             }
-            if self.codecov {
-                conflicts(flag, "--codecov")?;
+            if flag == "--text" && self.output_path.is_none() && self.output_dir.is_some() {
+                // written to the directory specified by --output-dir.
+                continue;
             }
+            default_destination.push(flag);
         }
-        if self.codecov {
-            let flag = "--codecov";
-            if self.json {
-                conflicts(flag, "--json")?;
-            }
-            if self.lcov {
-                conflicts(flag, "--lcov")?;
-            }
-            if self.cobertura {
-                conflicts(flag, "--cobertura")?;
-            }
+        if let [a, b, ..] = default_destination[..] {
+            let destination = if self.output_path.is_some() {
+                "the file specified by --output-path"
+            } else {
+                "stdout"
+            };
+            bail!(
+                "{a} and {b} cannot be written to {destination} together; consider using \
+                 `{a}=<PATH>` and `{b}=<PATH>` to specify the output path for each format"
+            );
         }
-        if self.text {
-            let flag = "--text";
-            if self.json {
-                conflicts(flag, "--json")?;
-            }
-            if self.lcov {
-                conflicts(flag, "--lcov")?;
-            }
-            if self.cobertura {
-                conflicts(flag, "--cobertura")?;
-            }
-            if self.codecov {
-                conflicts(flag, "--codecov")?;
+        // --annotations and --teamcity are always printed to stdout.
+        for (flag, passed) in
+            [("--annotations", self.annotations.is_some()), ("--teamcity", self.teamcity)]
+        {
+            if let (true, Some(&other), None) =
+                (passed, default_destination.first(), &self.output_path)
+            {
+                bail!(
+                    "{flag} and {other} cannot be written to stdout together; consider using \
+                     `{other}=<PATH>` to specify the output path for {other}"
+                );
             }
         }
-        if self.html || self.open {
-            let flag = if self.html { "--html" } else { "--open" };
-            if self.json {
-                conflicts(flag, "--json")?;
+        // The following flags are no-op for --html and --open, so reject them unless used
+        // together with other formats.
+        let export = self.json
+            || self.lcov
+            || self.cobertura
+            || self.codecov
+            || self.sonarqube
+            || self.coveralls
+            || self.jacoco
+            || self.markdown
+            || self.sarif
+            || self.csv
+            || self.istanbul
+            || self.openmetrics;
+        for (flag, passed) in [
+            ("--summary-only", self.summary_only && !export),
+            ("--output-path", self.output_path.is_some() && !(export || self.text)),
+            ("--skip-functions", self.skip_functions && !export),
+        ] {
+            if passed {
+                if self.html {
+                    conflicts(flag, "--html")?;
+                }
+                if self.open && flag != "--skip-functions" {
+                    conflicts(flag, "--open")?;
+                }
             }
-            if self.lcov {
-                conflicts(flag, "--lcov")?;
+        }
+        if self.output_dir.is_some() {
+            let flag = "--output-dir";
+            // --output-dir is no-op if only formats that write a single file are specified.
+            if !(self.text || self.html || self.open || self.gcov) {
+                if self.json {
+                    conflicts(flag, "--json")?;
+                }
+                if self.lcov {
+                    conflicts(flag, "--lcov")?;
+                }
+                if self.cobertura {
+                    conflicts(flag, "--cobertura")?;
+                }
+                if self.codecov {
+                    conflicts(flag, "--codecov")?;
+                }
+                if self.sonarqube {
+                    conflicts(flag, "--sonarqube")?;
+                }
+                if self.coveralls {
+                    conflicts(flag, "--coveralls")?;
+                }
+                if self.jacoco {
+                    conflicts(flag, "--jacoco")?;
+                }
+                if self.markdown {
+                    conflicts(flag, "--markdown")?;
+                }
+                if self.sarif {
+                    conflicts(flag, "--sarif")?;
+                }
+                if self.csv {
+                    conflicts(flag, "--csv")?;
+                }
+                if self.istanbul {
+                    conflicts(flag, "--istanbul")?;
+                }
+                if self.openmetrics {
+                    conflicts(flag, "--openmetrics")?;
+                }
             }
-            if self.cobertura {
-                conflicts(flag, "--cobertura")?;
+            if self.output_path.is_some() {
+                conflicts(flag, "--output-path")?;
             }
-            if self.codecov {
-                conflicts(flag, "--codecov")?;
+        }
+        if self.diff_base.is_some() && self.diff_file.is_some() {
+            conflicts("--diff-base", "--diff-file")?;
+        }
+
+        // requires
+        if self.fail_under_diff_lines.is_some()
+            && self.diff_base.is_none()
+            && self.diff_file.is_none()
+        {
+            requires("--fail-under-diff-lines", &["--diff-base", "--diff-file"])?;
+        }
+        if self.csv_functions && !self.csv {
+            requires("--csv-functions", &["--csv"])?;
+        }
+        if self.sarif_level.is_some() && !self.sarif {
+            requires("--sarif-level", &["--sarif"])?;
+        }
+        if self.badge.is_none() && self.shields_json.is_none() {
+            if self.badge_metric.is_some() {
+                requires("--badge-metric", &["--badge", "--shields-json"])?;
             }
-            if self.text {
-                conflicts(flag, "--text")?;
+            if self.badge_colors.is_some() {
+                requires("--badge-colors", &["--badge", "--shields-json"])?;
             }
         }
-        if self.summary_only || self.output_path.is_some() {
-            let flag = if self.summary_only { "--summary-only" } else { "--output-path" };
-            if self.html {
-                conflicts(flag, "--html")?;
+        if self.baseline.is_none() {
+            if self.baseline_tolerance.is_some() {
+                requires("--baseline-tolerance", &["--baseline"])?;
             }
-            if self.open {
-                conflicts(flag, "--open")?;
+            if self.update_baseline {
+                requires("--update-baseline", &["--baseline"])?;
             }
         }
-        if self.skip_functions {
-            let flag = "--skip-functions";
-            if self.html {
-                conflicts(flag, "--html")?;
+
+        Ok(())
+    }
+}
+
+/// Coverage threshold specified by --fail-under-{functions,lines,regions}.
+///
+/// The value is `<MIN>` for the total coverage, `<NAME>=<MIN>` for the coverage of the package,
+/// or a comma-separated list of them.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Threshold {
+    /// Threshold for the total coverage.
+    pub(crate) total: Option<f64>,
+    /// List of (package name, threshold) pairs.
+    pub(crate) packages: Vec<(String, f64)>,
+}
+
+impl FromStr for Threshold {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut threshold = Self::default();
+        for v in s.split(',').map(str::trim) {
+            if let Some((name, min)) = v.split_once('=') {
+                let min = min.parse().map_err(|e| format_err!("invalid threshold '{min}': {e}"))?;
+                threshold.packages.push((name.to_owned(), min));
+            } else if threshold.total.is_some() {
+                bail!("threshold for the total coverage was specified more than once: {s}");
+            } else {
+                threshold.total =
+                    Some(v.parse().map_err(|e| format_err!("invalid threshold '{v}': {e}"))?);
             }
         }
-        if self.output_dir.is_some() {
-            let flag = "--output-dir";
-            if self.json {
-                conflicts(flag, "--json")?;
-            }
-            if self.lcov {
-                conflicts(flag, "--lcov")?;
-            }
-            if self.cobertura {
-                conflicts(flag, "--cobertura")?;
-            }
-            if self.codecov {
-                conflicts(flag, "--codecov")?;
-            }
-            if self.output_path.is_some() {
-                conflicts(flag, "--output-path")?;
+        Ok(threshold)
+    }
+}
+
+/// Format of annotations printed by --annotations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Annotations {
+    /// GitHub Actions workflow commands (`::warning file=<FILE>,line=<LINE>::<MESSAGE>`).
+    GitHub,
+    /// Quickfix entries of editors (`<FILE>:<LINE>:<COLUMN>: <MESSAGE>`).
+    Quickfix,
+}
+
+impl FromStr for Annotations {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "github" => Ok(Self::GitHub),
+            "quickfix" => Ok(Self::Quickfix),
+            _ => bail!("unrecognized annotations format '{s}'; possible values: github, quickfix"),
+        }
+    }
+}
+
+/// Severity of each kind of results in the SARIF report, specified by --sarif-level.
+///
+/// The value is a comma-separated list of `<KIND>=<LEVEL>`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SarifLevels {
+    /// Level of uncovered functions.
+    pub(crate) function: SarifLevel,
+    /// Level of uncovered regions in covered functions.
+    pub(crate) region: SarifLevel,
+    /// Level of partially covered branches in covered functions.
+    pub(crate) branch: SarifLevel,
+}
+
+impl Default for SarifLevels {
+    fn default() -> Self {
+        Self { function: SarifLevel::Warning, region: SarifLevel::Note, branch: SarifLevel::Note }
+    }
+}
+
+impl FromStr for SarifLevels {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut levels = Self::default();
+        for v in s.split(',').map(str::trim) {
+            let Some((kind, level)) = v.split_once('=') else {
+                bail!("expected <KIND>=<LEVEL>, found '{v}'");
+            };
+            let level = match level {
+                "error" => SarifLevel::Error,
+                "warning" => SarifLevel::Warning,
+                "note" => SarifLevel::Note,
+                "none" => SarifLevel::None,
+                _ => bail!(
+                    "unrecognized SARIF level '{level}'; possible values: error, warning, note, none"
+                ),
+            };
+            match kind {
+                "function" => levels.function = level,
+                "region" => levels.region = level,
+                "branch" => levels.branch = level,
+                _ => bail!("unrecognized kind '{kind}'; possible values: function, region, branch"),
             }
         }
+        Ok(levels)
+    }
+}
 
-        Ok(())
+/// Level of results in the SARIF report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SarifLevel {
+    Error,
+    Warning,
+    Note,
+    None,
+}
+
+impl SarifLevel {
+    pub(crate) const fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Note => "note",
+            Self::None => "none",
+        }
+    }
+}
+
+/// Colors of the badge specified by --badge-colors.
+///
+/// The value is a comma-separated list of `<MIN>=<COLOR>`. The color of the highest MIN that the
+/// coverage reaches is used, or the color of the lowest MIN if the coverage reaches none of them.
+#[derive(Debug, Clone)]
+pub(crate) struct BadgeColors(
+    /// List of (MIN, COLOR) pairs, sorted by MIN in descending order.
+    Vec<(f64, String)>,
+);
+
+impl Default for BadgeColors {
+    fn default() -> Self {
+        Self(vec![
+            (80_f64, "brightgreen".to_owned()),
+            (60_f64, "yellow".to_owned()),
+            (0_f64, "red".to_owned()),
+        ])
+    }
+}
+
+impl BadgeColors {
+    pub(crate) fn color(&self, percent: f64) -> &str {
+        let (_, color) = self
+            .0
+            .iter()
+            .find(|(min, _)| percent >= *min)
+            .unwrap_or_else(|| self.0.last().unwrap());
+        color
+    }
+}
+
+impl FromStr for BadgeColors {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut colors = vec![];
+        for v in s.split(',').map(str::trim) {
+            let Some((min, color)) = v.split_once('=') else {
+                bail!("expected <MIN>=<COLOR>, found '{v}'");
+            };
+            let min: f64 =
+                min.parse().map_err(|e| format_err!("invalid minimum coverage '{min}': {e}"))?;
+            if badge::color_hex(color).is_none() {
+                bail!(
+                    "unrecognized color '{color}'; expected a named color of shields.io or a hex code"
+                );
+            }
+            colors.push((min, color.to_owned()));
+        }
+        colors.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        Ok(Self(colors))
     }
 }
 
@@ -558,7 +1072,7 @@ pub(crate) struct CleanOptions {
 impl CleanOptions {
     fn validate(&self, subcommand: Subcommand) -> Result<()> {
         let (no_clean_incompat, profraw_only_incompat) = match subcommand {
-            Subcommand::Report { .. } | Subcommand::ShowEnv => (true, true),
+            Subcommand::Report { .. } | Subcommand::ShowEnv | Subcommand::Select => (true, true),
             Subcommand::Clean => (true, false),
             Subcommand::None
             | Subcommand::Test
@@ -800,6 +1314,28 @@ pub(crate) mod escape {
     }
 }
 
+/// Options only referred in "select" operations. (select subcommand)
+#[derive(Debug, Default)]
+pub(crate) struct SelectOptions {
+    /// Path to per-test coverage data written by --per-test.
+    pub(crate) index: Option<Utf8PathBuf>,
+    /// Run the selected tests with `cargo nextest run` instead of printing the filter.
+    pub(crate) run: bool,
+}
+
+impl SelectOptions {
+    fn validate(&self, subcommand: Subcommand) -> Result<()> {
+        if subcommand != Subcommand::Select {
+            for (flag, passed) in [("--index", self.index.is_some()), ("--run", self.run)] {
+                if passed {
+                    specific_flag(flag, subcommand, &["select"])?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 // Arguments only referred in Context::new/Workspace::new.
 // It will be dropped at an early stage.
 pub(crate) struct UnresolvedArgs {
@@ -816,23 +1352,171 @@ pub(crate) struct UnresolvedArgs {
 
 pub(crate) fn merge_config_and_args(
     ws: &mut crate::cargo::Workspace,
-    target: &mut Option<String>,
-    verbose: &mut u8,
-    color: Option<Color>,
+    args: &mut Args,
+    unresolved_args: &mut UnresolvedArgs,
 ) -> Result<()> {
     // CLI flags are prefer over config values.
-    if target.is_none() {
-        target.clone_from(&ws.config.build_target_for_cli(None::<&str>)?.pop());
+    if args.verbose == 0 {
+        args.verbose = ws.config.term.verbose.unwrap_or(false) as u8;
     }
-    if *verbose == 0 {
-        *verbose = ws.config.term.verbose.unwrap_or(false) as u8;
-    }
-    if let Some(color) = color {
+    if let Some(color) = unresolved_args.color {
         ws.config.term.color = Some(color);
     }
+    // clean subcommand doesn't refer target and llvm-cov config.
+    if args.subcommand != Subcommand::Clean {
+        if args.target.is_none() {
+            args.target.clone_from(&ws.config.build_target_for_cli(None::<&str>)?.pop());
+        }
+        let config = LlvmCovConfig::load(ws)?;
+        merge_llvm_cov_config(config, args, &mut unresolved_args.exclude_from_report)?;
+    }
     Ok(())
 }
 
+fn merge_llvm_cov_config(
+    config: LlvmCovConfig,
+    args: &mut Args,
+    exclude_from_report: &mut Vec<String>,
+) -> Result<()> {
+    let LlvmCovConfig {
+        json,
+        lcov,
+        cobertura,
+        codecov,
+        sonarqube,
+        coveralls,
+        jacoco,
+        markdown,
+        sarif,
+        csv,
+        istanbul,
+        openmetrics,
+        gcov,
+        text,
+        html,
+        output_path,
+        output_dir,
+        ignore_filename_regex,
+        fail_under_functions,
+        fail_under_lines,
+        fail_under_file_lines,
+        fail_under_regions,
+        fail_uncovered_lines,
+        fail_uncovered_regions,
+        fail_uncovered_functions,
+        show_missing_lines,
+        package_summary,
+        baseline,
+        baseline_tolerance,
+        update_baseline,
+        include_all_sources,
+        doctests,
+        branch,
+        include_ffi,
+        exclude,
+        exclude_from_test,
+        exclude_from_report: config_exclude_from_report,
+    } = config;
+
+    // build options
+    if doctests
+        && matches!(
+            args.subcommand,
+            Subcommand::None | Subcommand::Test | Subcommand::ShowEnv | Subcommand::Report { .. }
+        )
+        && !args.build.has_target_selection_options
+    {
+        args.doctests = true;
+    }
+    if branch && !args.build.mcdc {
+        args.build.branch = true;
+    }
+    args.build.include_ffi |= include_ffi;
+    // --exclude and --exclude-from-test require --workspace.
+    if args.workspace {
+        for e in exclude {
+            if !exclude_from_report.contains(&e) {
+                exclude_from_report.push(e.clone());
+            }
+            args.build.cargo_args.push("--exclude".to_owned());
+            args.build.cargo_args.push(e);
+        }
+        for e in exclude_from_test {
+            if !args.build.exclude_from_test.contains(&e) {
+                args.build.exclude_from_test.push(e);
+            }
+        }
+    }
+    for e in config_exclude_from_report {
+        if !exclude_from_report.contains(&e) {
+            exclude_from_report.push(e);
+        }
+    }
+
+    // report options
+    if matches!(args.subcommand, Subcommand::ShowEnv | Subcommand::NextestArchive)
+        || args.report.no_report
+    {
+        return Ok(());
+    }
+    let report = &mut args.report;
+    if !(report.json
+        || report.lcov
+        || report.cobertura
+        || report.codecov
+        || report.sonarqube
+        || report.coveralls
+        || report.jacoco
+        || report.markdown
+        || report.sarif
+        || report.csv
+        || report.istanbul
+        || report.openmetrics
+        || report.gcov
+        || report.text
+        || report.html
+        || report.open)
+    {
+        (report.json, report.json_output_path) = ReportOutput::into_parts(json);
+        (report.lcov, report.lcov_output_path) = ReportOutput::into_parts(lcov);
+        (report.cobertura, report.cobertura_output_path) = ReportOutput::into_parts(cobertura);
+        (report.codecov, report.codecov_output_path) = ReportOutput::into_parts(codecov);
+        (report.sonarqube, report.sonarqube_output_path) = ReportOutput::into_parts(sonarqube);
+        (report.coveralls, report.coveralls_output_path) = ReportOutput::into_parts(coveralls);
+        (report.jacoco, report.jacoco_output_path) = ReportOutput::into_parts(jacoco);
+        (report.markdown, report.markdown_output_path) = ReportOutput::into_parts(markdown);
+        (report.sarif, report.sarif_output_path) = ReportOutput::into_parts(sarif);
+        (report.csv, report.csv_output_path) = ReportOutput::into_parts(csv);
+        (report.istanbul, report.istanbul_output_path) = ReportOutput::into_parts(istanbul);
+        (report.openmetrics, report.openmetrics_output_path) =
+            ReportOutput::into_parts(openmetrics);
+        (report.gcov, report.gcov_output_dir) = ReportOutput::into_parts(gcov);
+        (report.text, report.text_output_path) = ReportOutput::into_parts(text);
+        (report.html, report.html_output_dir) = ReportOutput::into_parts(html);
+        if report.output_path.is_none() && report.output_dir.is_none() {
+            report.output_path = output_path;
+            report.output_dir = output_dir;
+        }
+    }
+    if report.ignore_filename_regex.is_none() {
+        report.ignore_filename_regex = ignore_filename_regex;
+    }
+    report.fail_under_functions = report.fail_under_functions.take().or(fail_under_functions);
+    report.fail_under_lines = report.fail_under_lines.take().or(fail_under_lines);
+    report.fail_under_file_lines = report.fail_under_file_lines.or(fail_under_file_lines);
+    report.fail_under_regions = report.fail_under_regions.take().or(fail_under_regions);
+    report.fail_uncovered_lines = report.fail_uncovered_lines.or(fail_uncovered_lines);
+    report.fail_uncovered_regions = report.fail_uncovered_regions.or(fail_uncovered_regions);
+    report.fail_uncovered_functions = report.fail_uncovered_functions.or(fail_uncovered_functions);
+    report.show_missing_lines |= show_missing_lines;
+    report.package_summary |= package_summary;
+    report.baseline = report.baseline.take().or(baseline);
+    report.baseline_tolerance = report.baseline_tolerance.or(baseline_tolerance);
+    report.update_baseline |= update_baseline;
+    report.include_all_sources |= include_all_sources;
+    report.validate(args.subcommand).context("invalid combination of CLI flags and llvm-cov config")
+}
+
 pub(crate) const FIRST_SUBCMD: &str = "llvm-cov";
 
 impl Args {
@@ -907,6 +1591,7 @@ impl Args {
 
         let mut report = ReportOptions::default();
         let mut clean = CleanOptions::default();
+        let mut select = SelectOptions::default();
 
         // build options
         let mut release = false;
@@ -932,6 +1617,7 @@ impl Args {
         let mut cargo_profile = None;
         let mut archive_file = None;
         let mut nextest_archive_file = None;
+        let mut per_test = false;
 
         let mut parser = lexopt::Parser::from_args(args);
         while let Some(arg) = parser.next()? {
@@ -1004,6 +1690,15 @@ impl Args {
                     }
                 }};
             }
+            macro_rules! parse_format {
+                ($flag:ident.$field:ident, $path:ident.$path_field:ident $(,)?) => {{
+                    parse_flag!($flag.$field);
+                    // `--<format>=<PATH>` specifies the output path for the format.
+                    if let Some(val) = parser.optional_value() {
+                        $path.$path_field = Some(val.string()?.into());
+                    }
+                }};
+            }
             macro_rules! parse_flag_passthrough {
                 ($flag:tt $(.$field:ident)? $(,)?) => {{
                     parse_flag!($flag $(.$field)?);
@@ -1084,14 +1779,33 @@ impl Args {
                 // clean options
                 Long("profraw-only") => parse_flag!(clean.profraw_only),
 
+                // select options
+                Long("index") => parse_opt!(select.index),
+                Long("run") => parse_flag!(select.run),
+
                 // report options
                 Long("no-report") => parse_flag!(report.no_report),
-                Long("json") => parse_flag!(report.json),
-                Long("lcov") => parse_flag!(report.lcov),
-                Long("cobertura") => parse_flag!(report.cobertura),
-                Long("codecov") => parse_flag!(report.codecov),
-                Long("text") => parse_flag!(report.text),
-                Long("html") => parse_flag!(report.html),
+                Long("json") => parse_format!(report.json, report.json_output_path),
+                Long("lcov") => parse_format!(report.lcov, report.lcov_output_path),
+                Long("cobertura") => {
+                    parse_format!(report.cobertura, report.cobertura_output_path);
+                }
+                Long("codecov") => parse_format!(report.codecov, report.codecov_output_path),
+                Long("sonarqube") => parse_format!(report.sonarqube, report.sonarqube_output_path),
+                Long("coveralls") => parse_format!(report.coveralls, report.coveralls_output_path),
+                Long("jacoco") => parse_format!(report.jacoco, report.jacoco_output_path),
+                Long("markdown") => parse_format!(report.markdown, report.markdown_output_path),
+                Long("sarif") => parse_format!(report.sarif, report.sarif_output_path),
+                Long("csv") => parse_format!(report.csv, report.csv_output_path),
+                Long("istanbul") => parse_format!(report.istanbul, report.istanbul_output_path),
+                Long("openmetrics") => {
+                    parse_format!(report.openmetrics, report.openmetrics_output_path);
+                }
+                Long("csv-functions") => parse_flag!(report.csv_functions),
+                Long("gcov") => parse_format!(report.gcov, report.gcov_output_dir),
+                Long("sarif-level") => parse_opt!(report.sarif_level),
+                Long("text") => parse_format!(report.text, report.text_output_path),
+                Long("html") => parse_format!(report.html, report.html_output_dir),
                 Long("open") => parse_flag!(report.open),
                 Long("summary-only") => parse_flag!(report.summary_only),
                 Long("skip-functions") => parse_flag!(report.skip_functions),
@@ -1124,7 +1838,22 @@ impl Args {
                 Long("fail-uncovered-regions") => parse_opt!(report.fail_uncovered_regions),
                 Long("fail-uncovered-functions") => parse_opt!(report.fail_uncovered_functions),
                 Long("show-missing-lines") => parse_flag!(report.show_missing_lines),
+                Long("annotations") => parse_opt!(report.annotations),
+                Long("teamcity") => parse_flag!(report.teamcity),
+                Long("package-summary") => parse_flag!(report.package_summary),
+                Long("diff-base") => parse_opt!(report.diff_base),
+                Long("diff-file") => parse_opt!(report.diff_file),
+                Long("fail-under-diff-lines") => parse_opt!(report.fail_under_diff_lines),
+                Long("baseline") => parse_opt!(report.baseline),
+                Long("baseline-tolerance") => parse_opt!(report.baseline_tolerance),
+                Long("update-baseline") => parse_flag!(report.update_baseline),
+                Long("save-baseline") => parse_opt!(report.save_baseline),
+                Long("badge") => parse_opt!(report.badge),
+                Long("shields-json") => parse_opt!(report.shields_json),
+                Long("badge-metric") => parse_opt!(report.badge_metric),
+                Long("badge-colors") => parse_opt!(report.badge_colors),
                 Long("include-build-script") => parse_flag!(report.include_build_script),
+                Long("include-all-sources") => parse_flag!(report.include_all_sources),
 
                 // show-env options
                 Long(flag @ ("sh" | "export-prefix")) => {
@@ -1148,6 +1877,7 @@ impl Args {
                 // ambiguous between nextest-related and others will be handled later
                 Long("archive-file") => parse_opt_passthrough!(archive_file),
                 Long("nextest-archive-file") => parse_opt!(nextest_archive_file),
+                Long("per-test") => parse_flag!(per_test),
 
                 Short('v') | Long("verbose") => {
                     verbose += 1;
@@ -1248,6 +1978,14 @@ impl Args {
         report.validate(subcommand)?;
         // clean specific
         clean.validate(subcommand)?;
+        // select specific
+        select.validate(subcommand)?;
+        if subcommand == Subcommand::Select
+            && report.diff_base.is_none()
+            && report.diff_file.is_none()
+        {
+            bail!("select subcommand requires --diff-base or --diff-file");
+        }
         // show-env specific
         let show_env = ShowEnvOptions::new(subcommand, sh, pwsh, cmd, csh, fish, nu, xonsh)?;
         // test or show-env or report specific
@@ -1285,7 +2023,8 @@ impl Args {
             Subcommand::Run
             | Subcommand::Clean
             | Subcommand::Report { .. }
-            | Subcommand::ShowEnv => {
+            | Subcommand::ShowEnv
+            | Subcommand::Select => {
                 for (flag, passed) in [
                     ("--lib", lib),
                     ("--bins", bins),
@@ -1329,7 +2068,10 @@ impl Args {
             | Subcommand::Run
             | Subcommand::Nextest { .. }
             | Subcommand::NextestArchive => {}
-            Subcommand::Report { .. } | Subcommand::Clean | Subcommand::ShowEnv => {
+            Subcommand::Report { .. }
+            | Subcommand::Clean
+            | Subcommand::ShowEnv
+            | Subcommand::Select => {
                 for (flag, passed) in [
                     ("--bin", !bin.is_empty()),
                     ("--example", !example.is_empty()),
@@ -1357,7 +2099,7 @@ impl Args {
             | Subcommand::Nextest { .. }
             | Subcommand::NextestArchive
             | Subcommand::ShowEnv => {}
-            Subcommand::Report { .. } | Subcommand::Clean => {
+            Subcommand::Report { .. } | Subcommand::Clean | Subcommand::Select => {
                 for (flag, passed) in [
                     ("--no-cfg-coverage", no_cfg_coverage),
                     ("--no-cfg-coverage-nightly", no_cfg_coverage_nightly),
@@ -1384,7 +2126,7 @@ impl Args {
             | Subcommand::NextestArchive
             | Subcommand::Report { .. }
             | Subcommand::Clean => {}
-            Subcommand::Run | Subcommand::ShowEnv => {
+            Subcommand::Run | Subcommand::ShowEnv | Subcommand::Select => {
                 if workspace {
                     specific_flag("--workspace", subcommand, &[
                         "test",
@@ -1440,6 +2182,11 @@ impl Args {
                 }
             }
         }
+        // nextest or report specific
+        if per_test && !matches!(subcommand, Subcommand::Nextest { .. } | Subcommand::Report { .. })
+        {
+            specific_flag("--per-test", subcommand, &["nextest", "report"])?;
+        }
         // TODO: check more
 
         // requires
@@ -1524,8 +2271,29 @@ impl Args {
         // forbid_empty_values
         for (flag, is_empty) in [
             ("--ignore-filename-regex", report.ignore_filename_regex.as_deref() == Some("")),
+            ("--json", report.json_output_path.as_deref() == Some(Utf8Path::new(""))),
+            ("--lcov", report.lcov_output_path.as_deref() == Some(Utf8Path::new(""))),
+            ("--cobertura", report.cobertura_output_path.as_deref() == Some(Utf8Path::new(""))),
+            ("--codecov", report.codecov_output_path.as_deref() == Some(Utf8Path::new(""))),
+            ("--sonarqube", report.sonarqube_output_path.as_deref() == Some(Utf8Path::new(""))),
+            ("--coveralls", report.coveralls_output_path.as_deref() == Some(Utf8Path::new(""))),
+            ("--jacoco", report.jacoco_output_path.as_deref() == Some(Utf8Path::new(""))),
+            ("--markdown", report.markdown_output_path.as_deref() == Some(Utf8Path::new(""))),
+            ("--sarif", report.sarif_output_path.as_deref() == Some(Utf8Path::new(""))),
+            ("--csv", report.csv_output_path.as_deref() == Some(Utf8Path::new(""))),
+            ("--istanbul", report.istanbul_output_path.as_deref() == Some(Utf8Path::new(""))),
+            ("--openmetrics", report.openmetrics_output_path.as_deref() == Some(Utf8Path::new(""))),
+            ("--gcov", report.gcov_output_dir.as_deref() == Some(Utf8Path::new(""))),
+            ("--text", report.text_output_path.as_deref() == Some(Utf8Path::new(""))),
+            ("--html", report.html_output_dir.as_deref() == Some(Utf8Path::new(""))),
             ("--output-path", report.output_path.as_deref() == Some(Utf8Path::new(""))),
             ("--output-dir", report.output_dir.as_deref() == Some(Utf8Path::new(""))),
+            ("--diff-base", report.diff_base.as_deref() == Some("")),
+            ("--diff-file", report.diff_file.as_deref() == Some(Utf8Path::new(""))),
+            ("--baseline", report.baseline.as_deref() == Some(Utf8Path::new(""))),
+            ("--save-baseline", report.save_baseline.as_deref() == Some(Utf8Path::new(""))),
+            ("--badge", report.badge.as_deref() == Some(Utf8Path::new(""))),
+            ("--shields-json", report.shields_json.as_deref() == Some(Utf8Path::new(""))),
         ] {
             if is_empty {
                 bail!("empty string is not allowed in {flag}")
@@ -1597,8 +2365,8 @@ impl Args {
             // --no-run is deprecated alias for report
             subcommand = Subcommand::Report { nextest_archive_file: false };
         }
-        if report.output_dir.is_some() && !report.show() {
-            // If the format flag is not specified, this flag is no-op.
+        if report.output_dir.is_some() && !report.show() && !report.gcov && !per_test {
+            // If neither the format flag nor --per-test is specified, this flag is no-op.
             // TODO: warn
             report.output_dir = None;
         }
@@ -1639,6 +2407,7 @@ impl Args {
                 report,
                 clean,
                 show_env,
+                select,
                 doctests,
                 workspace,
                 release,
@@ -1647,6 +2416,7 @@ impl Args {
                 verbose: verbose.try_into().unwrap_or(u8::MAX),
                 remap_path_prefix,
                 dep_coverage,
+                per_test,
                 nextest_archive_file,
             },
             UnresolvedArgs { package, exclude_from_report, manifest_path, color },
@@ -0,0 +1,243 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Cobertura XML report.
+// Refs: https://cobertura.sourceforge.net/xml/coverage-04.dtd
+//
+// Each package is a crate and each class is a file in it. File names are relative to the
+// workspace root, which is the only source directory.
+
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use camino::Utf8Path;
+use cargo_llvm_cov::json::{self, File, Function, LineBranchCounts, LlvmCovJsonExport, Summary};
+
+use crate::xml;
+
+pub(crate) struct Options<'a> {
+    pub(crate) workspace_root: &'a Utf8Path,
+    /// `true` if file names are relative to the workspace root (--remap-path-prefix).
+    pub(crate) remap_path_prefix: bool,
+    pub(crate) skip_functions: bool,
+    /// Seconds since the Unix epoch.
+    pub(crate) timestamp: u64,
+}
+
+struct Class<'a> {
+    /// Path relative to the package directory without extension, or file stem if not in any
+    /// package.
+    name: String,
+    file: &'a File,
+    functions: Vec<&'a Function>,
+}
+
+/// Returns the report in Cobertura XML format.
+///
+/// `package_of` returns the name and the directory of the package that contains the given file.
+/// Files that are not in any package are grouped by the directory that contains them.
+pub(crate) fn to_string<'a>(
+    json: &'a LlvmCovJsonExport,
+    options: &Options<'_>,
+    package_of: impl Fn(&str) -> Option<(&'a str, &'a Utf8Path)>,
+) -> String {
+    let mut packages: BTreeMap<&str, Vec<Class<'_>>> = BTreeMap::new();
+    for data in &json.data {
+        for file in &data.files {
+            let (package, name) = match package_of(&file.filename) {
+                Some((package, dir)) => {
+                    // File names are relative to the workspace root if --remap-path-prefix is
+                    // used.
+                    let path = options.workspace_root.join(&file.filename);
+                    let path = path.strip_prefix(dir).unwrap_or(&path);
+                    (package, path.with_extension("").as_str().replace('/', "."))
+                }
+                None => {
+                    let path = Utf8Path::new(&file.filename);
+                    (
+                        path.parent().map_or("", Utf8Path::as_str),
+                        path.file_stem().unwrap_or(&file.filename).to_owned(),
+                    )
+                }
+            };
+            let functions = if options.skip_functions {
+                vec![]
+            } else {
+                data.functions_in(&file.filename).collect()
+            };
+            packages.entry(package).or_default().push(Class { name, file, functions });
+        }
+    }
+    let package_summaries: BTreeMap<_, _> = packages
+        .iter()
+        .map(|(&name, classes)| {
+            let mut summary = Summary::default();
+            for class in classes {
+                summary.add(&class.file.summary);
+            }
+            (name, summary)
+        })
+        .collect();
+    let mut total = Summary::default();
+    for summary in package_summaries.values() {
+        total.add(summary);
+    }
+
+    let mut out = String::new();
+    out.push_str(
+        "<?xml version=\"1.0\" ?>\n\
+         <!DOCTYPE coverage SYSTEM \"http://cobertura.sourceforge.net/xml/coverage-04.dtd\">\n",
+    );
+    let _ = writeln!(
+        out,
+        "<coverage line-rate=\"{}\" branch-rate=\"{}\" lines-covered=\"{}\" lines-valid=\"{}\" \
+         branches-covered=\"{}\" branches-valid=\"{}\" complexity=\"0\" version=\"1.9\" \
+         timestamp=\"{}\">",
+        rate(total.lines.covered, total.lines.count),
+        rate(total.branches.covered, total.branches.count),
+        total.lines.covered,
+        total.lines.count,
+        total.branches.covered,
+        total.branches.count,
+        options.timestamp,
+    );
+    let source = if options.remap_path_prefix { "." } else { options.workspace_root.as_str() };
+    let _ =
+        writeln!(out, "  <sources>\n    <source>{}</source>\n  </sources>", xml::escape(source));
+    out.push_str("  <packages>\n");
+    for (package, classes) in &packages {
+        let summary = &package_summaries[package];
+        let _ = writeln!(
+            out,
+            "    <package name=\"{}\" line-rate=\"{}\" branch-rate=\"{}\" complexity=\"0\">",
+            xml::escape(package),
+            rate(summary.lines.covered, summary.lines.count),
+            rate(summary.branches.covered, summary.branches.count),
+        );
+        out.push_str("      <classes>\n");
+        for class in classes {
+            write_class(&mut out, class, options.workspace_root);
+        }
+        out.push_str("      </classes>\n    </package>\n");
+    }
+    out.push_str("  </packages>\n</coverage>\n");
+    out
+}
+
+fn write_class(out: &mut String, class: &Class<'_>, workspace_root: &Utf8Path) {
+    let lines = class.file.line_branch_counts();
+
+    let filename = Utf8Path::new(&class.file.filename)
+        .strip_prefix(workspace_root)
+        .map_or(&*class.file.filename, Utf8Path::as_str);
+    let summary = &class.file.summary;
+    let _ = writeln!(
+        out,
+        "        <class name=\"{}\" filename=\"{}\" line-rate=\"{}\" branch-rate=\"{}\" \
+         complexity=\"0\">",
+        xml::escape(&class.name),
+        xml::escape(filename),
+        rate(summary.lines.covered, summary.lines.count),
+        rate(summary.branches.covered, summary.branches.count),
+    );
+
+    // Instantiations of the same generic function are merged.
+    let mut methods: BTreeMap<(u64, String), u64> = BTreeMap::new();
+    for f in &class.functions {
+        let end = methods.entry((f.start_line(), json::demangle(f.name()))).or_default();
+        *end = (*end).max(f.end_line());
+    }
+    if methods.is_empty() {
+        out.push_str("          <methods/>\n");
+    } else {
+        out.push_str("          <methods>\n");
+        for ((start, name), end) in methods {
+            let method_lines: Vec<_> = lines.range(start..=end.max(start)).collect();
+            let (mut lines_covered, mut branches, mut branches_covered) = (0, 0, 0);
+            for (_, counts) in &method_lines {
+                lines_covered += u64::from(counts.count != 0);
+                branches += counts.branches;
+                branches_covered += counts.covered_branches;
+            }
+            let _ = writeln!(
+                out,
+                "            <method name=\"{}\" signature=\"\" line-rate=\"{}\" \
+                 branch-rate=\"{}\" complexity=\"0\">",
+                xml::escape(&name),
+                rate(lines_covered, method_lines.len() as u64),
+                rate(branches_covered, branches),
+            );
+            if method_lines.is_empty() {
+                out.push_str("              <lines/>\n");
+            } else {
+                out.push_str("              <lines>\n");
+                for (line, counts) in method_lines {
+                    write_line(out, "                ", *line, counts);
+                }
+                out.push_str("              </lines>\n");
+            }
+            out.push_str("            </method>\n");
+        }
+        out.push_str("          </methods>\n");
+    }
+
+    if lines.is_empty() {
+        out.push_str("          <lines/>\n");
+    } else {
+        out.push_str("          <lines>\n");
+        for (line, counts) in &lines {
+            write_line(out, "            ", *line, counts);
+        }
+        out.push_str("          </lines>\n");
+    }
+    out.push_str("        </class>\n");
+}
+
+fn write_line(out: &mut String, indent: &str, line: u64, counts: &LineBranchCounts) {
+    let &LineBranchCounts { count, branches, covered_branches } = counts;
+    let _ = write!(out, "{indent}<line number=\"{line}\" hits=\"{count}\"");
+    if let Some(percent) = (covered_branches * 100).checked_div(branches) {
+        let _ = writeln!(
+            out,
+            " branch=\"true\" condition-coverage=\"{percent}% ({covered_branches}/{branches})\"/>"
+        );
+    } else {
+        out.push_str(" branch=\"false\"/>\n");
+    }
+}
+
+/// Returns the rate of covered items. Same as Cobertura, this is 1 if there is nothing to cover.
+#[allow(clippy::cast_precision_loss)]
+fn rate(covered: u64, valid: u64) -> f64 {
+    if valid == 0 { 1.0 } else { covered as f64 / valid as f64 }
+}
+
+#[cfg(test)]
+mod tests {
+    use camino::Utf8Path;
+    use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+    use crate::fs;
+
+    #[test]
+    fn cobertura() {
+        let model =
+            format!("{}/tests/fixtures/show-missing-lines-branches", env!("CARGO_MANIFEST_DIR"));
+        let mut json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(format!("{model}.json")).unwrap()).unwrap();
+        json["data"][0]["files"][0]["filename"] = "/ws/t/src/lib.rs".into();
+        for f in json["data"][0]["functions"].as_array_mut().unwrap() {
+            f["filenames"][0] = "/ws/t/src/lib.rs".into();
+        }
+        let json: LlvmCovJsonExport = serde_json::from_value(json).unwrap();
+        let options = super::Options {
+            workspace_root: "/ws".into(),
+            remap_path_prefix: false,
+            skip_functions: false,
+            timestamp: 0,
+        };
+        let out = super::to_string(&json, &options, |f| {
+            f.starts_with("/ws/t/").then_some(("t", Utf8Path::new("/ws/t")))
+        });
+        let expected = fs::read_to_string(format!("{model}.cobertura.xml")).unwrap();
+        assert_eq!(out, expected);
+    }
+}
@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// cargo-llvm-cov specific configuration.
+//
+// The configuration is read from the following sources (the former takes precedence):
+// - `[package.metadata.llvm-cov]` table in Cargo.toml of the current package
+// - `[workspace.metadata.llvm-cov]` table in Cargo.toml of the workspace root
+// - `.cargo/llvm-cov.toml` in the workspace root
+//
+// Values passed via CLI flags take precedence over all of them. See cli::merge_config_and_args.
+
+use anyhow::{Context as _, Result};
+use camino::Utf8PathBuf;
+use serde::de::{self, Deserialize as _, Deserializer};
+use serde_derive::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{cargo::Workspace, cli::Threshold, fs};
+
+const TABLE: &str = "llvm-cov";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct LlvmCovConfig {
+    // Report formats and their output destination. These are ignored if any report format is
+    // specified via CLI flags.
+    pub(crate) json: Option<ReportOutput>,
+    pub(crate) lcov: Option<ReportOutput>,
+    pub(crate) cobertura: Option<ReportOutput>,
+    pub(crate) codecov: Option<ReportOutput>,
+    pub(crate) sonarqube: Option<ReportOutput>,
+    pub(crate) coveralls: Option<ReportOutput>,
+    pub(crate) jacoco: Option<ReportOutput>,
+    pub(crate) markdown: Option<ReportOutput>,
+    pub(crate) sarif: Option<ReportOutput>,
+    pub(crate) csv: Option<ReportOutput>,
+    pub(crate) istanbul: Option<ReportOutput>,
+    pub(crate) openmetrics: Option<ReportOutput>,
+    pub(crate) gcov: Option<ReportOutput>,
+    pub(crate) text: Option<ReportOutput>,
+    pub(crate) html: Option<ReportOutput>,
+    pub(crate) output_path: Option<Utf8PathBuf>,
+    pub(crate) output_dir: Option<Utf8PathBuf>,
+
+    pub(crate) ignore_filename_regex: Option<String>,
+    #[serde(deserialize_with = "threshold")]
+    pub(crate) fail_under_functions: Option<Threshold>,
+    #[serde(deserialize_with = "threshold")]
+    pub(crate) fail_under_lines: Option<Threshold>,
+    pub(crate) fail_under_file_lines: Option<f64>,
+    #[serde(deserialize_with = "threshold")]
+    pub(crate) fail_under_regions: Option<Threshold>,
+    pub(crate) fail_uncovered_lines: Option<u64>,
+    pub(crate) fail_uncovered_regions: Option<u64>,
+    pub(crate) fail_uncovered_functions: Option<u64>,
+    pub(crate) show_missing_lines: bool,
+    pub(crate) package_summary: bool,
+    pub(crate) baseline: Option<Utf8PathBuf>,
+    pub(crate) baseline_tolerance: Option<f64>,
+    pub(crate) update_baseline: bool,
+    pub(crate) include_all_sources: bool,
+
+    pub(crate) doctests: bool,
+    pub(crate) branch: bool,
+    pub(crate) include_ffi: bool,
+    /// Packages excluded from both the test and the report. Only used together with --workspace.
+    pub(crate) exclude: Vec<String>,
+    /// Packages excluded from the test. Only used together with --workspace.
+    pub(crate) exclude_from_test: Vec<String>,
+    pub(crate) exclude_from_report: Vec<String>,
+}
+
+/// Value of report format keys: `true` to enable the format, or the output path.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum ReportOutput {
+    Enabled(bool),
+    Path(Utf8PathBuf),
+}
+
+impl ReportOutput {
+    /// Returns (enabled, output path).
+    pub(crate) fn into_parts(output: Option<Self>) -> (bool, Option<Utf8PathBuf>) {
+        match output {
+            Some(Self::Enabled(enabled)) => (enabled, None),
+            Some(Self::Path(path)) => (true, Some(path)),
+            None => (false, None),
+        }
+    }
+}
+
+impl LlvmCovConfig {
+    pub(crate) fn load(ws: &Workspace) -> Result<Self> {
+        let mut merged = Map::new();
+
+        let path = ws.metadata.workspace_root.join(".cargo").join("llvm-cov.toml");
+        if path.is_file() {
+            let value: Value = toml::from_str(&fs::read_to_string(&path)?)
+                .with_context(|| format!("failed to parse {path}"))?;
+            merge(&mut merged, value).with_context(|| format!("invalid config in {path}"))?;
+        }
+        if let Some(value) = ws.metadata.workspace_metadata.get(TABLE) {
+            merge(&mut merged, value.clone()).with_context(|| {
+                format!(
+                    "invalid config in [workspace.metadata.{TABLE}] table in {}",
+                    ws.metadata.workspace_root.join("Cargo.toml")
+                )
+            })?;
+        }
+        if let Some(id) = ws.current_package {
+            let pkg = &ws.metadata[id];
+            if let Some(value) = pkg.metadata.get(TABLE) {
+                merge(&mut merged, value.clone()).with_context(|| {
+                    format!(
+                        "invalid config in [package.metadata.{TABLE}] table in {}",
+                        pkg.manifest_path
+                    )
+                })?;
+            }
+        }
+
+        let mut config: Self = serde_json::from_value(Value::Object(merged))?;
+        // Relative paths in the config are relative to the workspace root.
+        let format_paths = [
+            &mut config.json,
+            &mut config.lcov,
+            &mut config.cobertura,
+            &mut config.codecov,
+            &mut config.sonarqube,
+            &mut config.coveralls,
+            &mut config.jacoco,
+            &mut config.markdown,
+            &mut config.sarif,
+            &mut config.csv,
+            &mut config.istanbul,
+            &mut config.openmetrics,
+            &mut config.gcov,
+            &mut config.text,
+            &mut config.html,
+        ]
+        .into_iter()
+        .filter_map(|output| match output {
+            Some(ReportOutput::Path(path)) => Some(path),
+            _ => None,
+        });
+        for path in [&mut config.output_path, &mut config.output_dir, &mut config.baseline]
+            .into_iter()
+            .flatten()
+            .chain(format_paths)
+        {
+            *path = ws.metadata.workspace_root.join(&*path);
+        }
+        Ok(config)
+    }
+}
+
+/// Overwrites keys in `merged` with keys in `value`.
+fn merge(merged: &mut Map<String, Value>, value: Value) -> Result<()> {
+    // Check the value here to report where the invalid value came from.
+    LlvmCovConfig::deserialize(&value)?;
+    if let Value::Object(map) = value {
+        merged.extend(map);
+    }
+    Ok(())
+}
+
+/// Deserializes `<MIN>` (number) or the string accepted by --fail-under-{functions,lines,regions}.
+fn threshold<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Threshold>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Number(f64),
+        String(String),
+    }
+    match Repr::deserialize(deserializer)? {
+        Repr::Number(min) => Ok(Some(Threshold { total: Some(min), packages: vec![] })),
+        Repr::String(s) => s.parse().map(Some).map_err(de::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn merge_config() {
+        let mut merged = Map::new();
+        merge(
+            &mut merged,
+            json!({ "lcov": true, "output-path": "lcov.info", "fail-under-lines": "80,foo=90" }),
+        )
+        .unwrap();
+        merge(
+            &mut merged,
+            json!({ "fail-under-lines": 70, "exclude": ["bar"], "cobertura": "cobertura.xml" }),
+        )
+        .unwrap();
+        let config: LlvmCovConfig = serde_json::from_value(Value::Object(merged)).unwrap();
+        assert_eq!(ReportOutput::into_parts(config.lcov), (true, None));
+        assert_eq!(
+            ReportOutput::into_parts(config.cobertura),
+            (true, Some("cobertura.xml".into()))
+        );
+        assert_eq!(config.output_path.as_deref().unwrap(), "lcov.info");
+        let fail_under_lines = config.fail_under_lines.unwrap();
+        assert_eq!(fail_under_lines.total, Some(70.));
+        assert!(fail_under_lines.packages.is_empty());
+        assert_eq!(config.exclude, ["bar"]);
+
+        assert!(merge(&mut Map::new(), json!({ "unknown": true })).is_err());
+        assert!(merge(&mut Map::new(), json!({ "fail-under-lines": "foo=bar" })).is_err());
+    }
+}
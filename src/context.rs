@@ -48,19 +48,14 @@ pub(crate) struct Context {
 }
 
 impl Context {
-    pub(crate) fn new((mut args, unresolved_args): (Args, UnresolvedArgs)) -> Result<Self> {
+    pub(crate) fn new((mut args, mut unresolved_args): (Args, UnresolvedArgs)) -> Result<Self> {
         let show_env = args.subcommand == Subcommand::ShowEnv;
         let mut ws = Workspace::new(
             unresolved_args.manifest_path.as_deref(),
             args.target.as_deref(),
             show_env,
         )?;
-        cli::merge_config_and_args(
-            &mut ws,
-            &mut args.target,
-            &mut args.verbose,
-            unresolved_args.color,
-        )?;
+        cli::merge_config_and_args(&mut ws, &mut args, &mut unresolved_args)?;
         term::set_coloring(&mut ws.config.term.color);
         term::verbose::set(args.verbose != 0);
 
@@ -85,8 +80,13 @@ impl Context {
             }
             info!("cargo-llvm-cov currently setting {cfgs}; you can opt-out it by passing {flags}");
         }
-        if args.report.output_dir.is_none() && args.report.html {
-            args.report.output_dir = Some(ws.default_output_dir.clone());
+        if args.report.html && args.report.html_output_dir.is_none() {
+            let output_dir = args.report.output_dir.as_ref().unwrap_or(&ws.default_output_dir);
+            args.report.html_output_dir = Some(output_dir.join("html"));
+        }
+        if args.report.gcov && args.report.gcov_output_dir.is_none() {
+            let output_dir = args.report.output_dir.as_ref().unwrap_or(&ws.default_output_dir);
+            args.report.gcov_output_dir = Some(output_dir.join("gcov"));
         }
         if !matches!(args.subcommand, Subcommand::Report { .. } | Subcommand::Clean)
             && env::var_os("CARGO_LLVM_COV_SHOW_ENV").is_some()
@@ -593,6 +593,7 @@ mod tests {
             version: "1.4.3".into(),
             targets: Box::default(),
             manifest_path: "".into(),
+            metadata: serde_json::Value::Null,
         };
         // name
         assert!(match_pkg_spec(pkg, "regex").unwrap());
@@ -630,6 +631,7 @@ mod tests {
             version: "1.4.3".into(),
             targets: Box::default(),
             manifest_path: "".into(),
+            metadata: serde_json::Value::Null,
         };
         assert!(match_pkg_spec(pkg, "regex").unwrap());
         assert!(
@@ -652,6 +654,7 @@ mod tests {
             version: "0.52.0".into(),
             targets: Box::default(),
             manifest_path: "".into(),
+            metadata: serde_json::Value::Null,
         };
         assert!(match_pkg_spec(pkg, "https://github.com/rust-lang/cargo#0.52.0").unwrap());
         assert!(match_pkg_spec(pkg, "git+https://github.com/rust-lang/cargo#0.52.0").unwrap());
@@ -667,6 +670,7 @@ mod tests {
             version: "1.1.8".into(),
             targets: Box::default(),
             manifest_path: "".into(),
+            metadata: serde_json::Value::Null,
         };
         assert!(match_pkg_spec(pkg, "foo").unwrap());
         assert!(match_pkg_spec(pkg, "file:///path/to/my/project/foo").unwrap());
@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Coveralls API job JSON.
+// Refs: https://docs.coveralls.io/api-reference
+
+use anyhow::Result;
+use camino::Utf8Path;
+use cargo_llvm_cov::json::LlvmCovJsonExport;
+use serde_derive::Serialize;
+
+use crate::fs;
+
+#[derive(Serialize)]
+struct Job {
+    source_files: Vec<SourceFile>,
+}
+
+#[derive(Serialize)]
+struct SourceFile {
+    /// File path relative to the workspace root if it is in the workspace.
+    name: String,
+    /// MD5 digest of the source code.
+    source_digest: String,
+    /// Execution count of each line, or `None` for lines that contain no code.
+    coverage: Vec<Option<u64>>,
+    /// `[line, block, branch, hits]` of each branch, flattened.
+    branches: Vec<u64>,
+}
+
+/// Returns the report in Coveralls API job JSON format.
+///
+/// Source files are read to compute the digest and the number of lines. Files that cannot be
+/// read (e.g., removed after collecting coverage) are skipped with a warning.
+pub(crate) fn to_string(json: &LlvmCovJsonExport, workspace_root: &Utf8Path) -> Result<String> {
+    let mut source_files = vec![];
+    for data in &json.data {
+        for file in &data.files {
+            // File names are relative to the workspace root if --remap-path-prefix is used.
+            let path = workspace_root.join(&file.filename);
+            let source = match fs::read(&path) {
+                Ok(source) => source,
+                Err(e) => {
+                    warn!("skipped {path} in coveralls report: {e}");
+                    continue;
+                }
+            };
+            let mut coverage = vec![None; source.split(|&b| b == b'\n').count()];
+            if source.ends_with(b"\n") {
+                coverage.pop();
+            }
+            for (line, count) in file.line_execution_counts() {
+                let Some(line) = usize::try_from(line).ok().and_then(|l| coverage.get_mut(l - 1))
+                else {
+                    continue;
+                };
+                *line = Some(count);
+            }
+            let mut branches = vec![];
+            // Branches on the same line are numbered in the same way as lcov.
+            for line_branches in file.branch_execution_counts().chunk_by(|a, b| a.line == b.line) {
+                for (block, branch) in line_branches.iter().enumerate() {
+                    let block = block as u64;
+                    branches.extend([branch.line, block, block * 2, branch.true_count]);
+                    branches.extend([branch.line, block, block * 2 + 1, branch.false_count]);
+                }
+            }
+            let name = Utf8Path::new(&file.filename)
+                .strip_prefix(workspace_root)
+                .map_or(&*file.filename, Utf8Path::as_str);
+            source_files.push(SourceFile {
+                name: name.to_owned(),
+                source_digest: format!("{:x}", md5::compute(&source)),
+                coverage,
+                branches,
+            });
+        }
+    }
+    Ok(serde_json::to_string(&Job { source_files })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+    use crate::fs;
+
+    #[test]
+    fn coveralls() {
+        let root = format!("{}/tests/fixtures/show-missing-lines", env!("CARGO_MANIFEST_DIR"));
+        let json: LlvmCovJsonExport =
+            serde_json::from_str(&fs::read_to_string(format!("{root}-branches.json")).unwrap())
+                .unwrap();
+        let out: serde_json::Value =
+            serde_json::from_str(&super::to_string(&json, root.as_str().into()).unwrap()).unwrap();
+        assert_eq!(
+            out,
+            serde_json::json!({
+                "source_files": [{
+                    "name": "src/lib.rs",
+                    "source_digest": "615c3d60c7c62c65730cca84b4bf997f",
+                    "coverage": [
+                        1, null, 1, 1, 1, null, 0, 0, 0, null, null, null, null, 1, 1, 1, 1, null,
+                    ],
+                    "branches": [4, 0, 0, 1, 4, 0, 1, 0],
+                }],
+            })
+        );
+    }
+}
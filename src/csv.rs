@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// CSV report of the coverage of each file or function.
+// Refs: https://www.rfc-editor.org/rfc/rfc4180
+
+use std::fmt::Write as _;
+
+use camino::Utf8Path;
+use cargo_llvm_cov::json::{self, LlvmCovJsonExport};
+
+const FILE_HEADER: &str = "package,path,\
+    lines_count,lines_covered,lines_percent,\
+    regions_count,regions_covered,regions_percent,\
+    functions_count,functions_covered,functions_percent,\
+    branches_count,branches_covered,branches_percent,\
+    instantiations_count,instantiations_covered,instantiations_percent";
+
+const FUNCTION_HEADER: &str = "package,path,function,line,execution_count,\
+    regions_count,regions_covered,regions_percent,\
+    branches_count,branches_covered,branches_percent";
+
+/// Returns the coverage of each file in CSV format.
+///
+/// `package_of` returns the name of the package that contains the given file.
+pub(crate) fn files_to_string<'a>(
+    json: &LlvmCovJsonExport,
+    workspace_root: &Utf8Path,
+    package_of: impl Fn(&str) -> Option<&'a str>,
+) -> String {
+    let mut out = String::new();
+    out.push_str(FILE_HEADER);
+    out.push('\n');
+    for file in json.data.iter().flat_map(|data| &data.files) {
+        write_field(&mut out, package_of(&file.filename).unwrap_or_default());
+        out.push(',');
+        write_field(&mut out, relative_path(workspace_root, &file.filename));
+        let s = &file.summary;
+        for counts in [&s.lines, &s.regions, &s.functions, &s.branches, &s.instantiations] {
+            write_counts(&mut out, counts.count, counts.covered);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Returns the coverage of each function in CSV format.
+///
+/// Instantiations of generic functions are written as separate rows.
+pub(crate) fn functions_to_string<'a>(
+    json: &LlvmCovJsonExport,
+    workspace_root: &Utf8Path,
+    package_of: impl Fn(&str) -> Option<&'a str>,
+) -> String {
+    let mut out = String::new();
+    out.push_str(FUNCTION_HEADER);
+    out.push('\n');
+    for data in &json.data {
+        for file in &data.files {
+            let package = package_of(&file.filename).unwrap_or_default();
+            let path = relative_path(workspace_root, &file.filename);
+            for f in data.functions_in(&file.filename) {
+                write_field(&mut out, package);
+                out.push(',');
+                write_field(&mut out, path);
+                out.push(',');
+                write_field(&mut out, &json::demangle(f.name()));
+                let _ = write!(out, ",{},{}", f.start_line(), f.count());
+                // Regions and branches of macro expansions are in other files.
+                let (mut count, mut covered) = (0, 0);
+                for (_, _, execution_count) in
+                    f.code_regions().filter(|&(name, ..)| name == file.filename)
+                {
+                    count += 1;
+                    covered += u64::from(execution_count != 0);
+                }
+                write_counts(&mut out, count, covered);
+                let (mut count, mut covered) = (0, 0);
+                for (_, _, branch) in f.branch_regions().filter(|&(name, ..)| name == file.filename)
+                {
+                    count += 2;
+                    covered +=
+                        u64::from(branch.true_count != 0) + u64::from(branch.false_count != 0);
+                }
+                write_counts(&mut out, count, covered);
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+fn relative_path<'a>(workspace_root: &Utf8Path, filename: &'a str) -> &'a str {
+    Utf8Path::new(filename).strip_prefix(workspace_root).map_or(filename, Utf8Path::as_str)
+}
+
+/// Writes `,count,covered,percent`. The percentage is empty if there is nothing to cover.
+#[allow(clippy::cast_precision_loss)]
+fn write_counts(out: &mut String, count: u64, covered: u64) {
+    let _ = write!(out, ",{count},{covered},");
+    if count != 0 {
+        let _ = write!(out, "{:.2}", covered as f64 * 100_f64 / count as f64);
+    }
+}
+
+fn write_field(out: &mut String, field: &str) {
+    if field.contains([',', '"', '\r', '\n']) {
+        let _ = write!(out, "\"{}\"", field.replace('"', "\"\""));
+    } else {
+        out.push_str(field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+    use crate::fs;
+
+    #[test]
+    fn csv() {
+        let file = format!(
+            "{}/tests/fixtures/show-missing-lines-branches.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let json: LlvmCovJsonExport =
+            serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+        let package_of = |_: &str| Some("t");
+        assert_eq!(
+            super::files_to_string(&json, "/ws".into(), package_of),
+            "package,path,\
+             lines_count,lines_covered,lines_percent,\
+             regions_count,regions_covered,regions_percent,\
+             functions_count,functions_covered,functions_percent,\
+             branches_count,branches_covered,branches_percent,\
+             instantiations_count,instantiations_covered,instantiations_percent\n\
+             t,src/lib.rs,11,8,72.73,8,4,50.00,5,4,80.00,2,1,50.00,5,4,80.00\n"
+        );
+        assert_eq!(
+            super::functions_to_string(&json, "/ws".into(), package_of),
+            "package,path,function,line,execution_count,\
+             regions_count,regions_covered,regions_percent,\
+             branches_count,branches_covered,branches_percent\n\
+             t,src/lib.rs,t::bar,7,0,3,0,0.00,0,0,\n\
+             t,src/lib.rs,t::tests::it_works::{closure#0},14,1,1,1,100.00,0,0,\n\
+             t,src/lib.rs,t::tests::it_works,15,1,1,1,100.00,0,0,\n\
+             t,src/lib.rs,t::foo,3,1,2,1,50.00,2,1,50.00\n\
+             t,src/lib.rs,t::main,1,1,1,1,100.00,0,0,\n"
+        );
+    }
+}
@@ -0,0 +1,305 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Refs:
+// - https://www.gnu.org/software/diffutils/manual/html_node/Detailed-Unified.html
+// - https://git-scm.com/docs/git-diff
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Context as _, Result, bail};
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::fs;
+
+/// Files (absolute path) -> list of changed lines.
+pub(crate) type ChangedLines = BTreeMap<Utf8PathBuf, BTreeSet<u64>>;
+
+#[derive(Debug, Default)]
+pub(crate) struct Diff {
+    /// Lines added or modified, in the new files.
+    pub(crate) new_lines: ChangedLines,
+    /// Lines removed or modified, and lines next to the added lines, in the old files.
+    pub(crate) old_lines: ChangedLines,
+}
+
+/// Collects lines changed relative to the given git revision.
+///
+/// Uncommitted changes in the working tree are also included, and untracked files (except for
+/// ignored files) are treated as added files.
+pub(crate) fn from_git(workspace_root: &Utf8Path, base: &str) -> Result<Diff> {
+    let repository_root = repository_root(workspace_root)
+        .with_context(|| format!("--diff-base requires {workspace_root} to be a git repository"))?;
+    // Pin the options that affect file paths in the output, because they can be changed by
+    // the user's git config (diff.noprefix, diff.mnemonicPrefix, and diff.relative).
+    let patch = cmd!(
+        "git",
+        "diff",
+        "--no-color",
+        "--no-ext-diff",
+        "--src-prefix=a/",
+        "--dst-prefix=b/",
+        "--no-relative",
+        "--unified=0",
+        base,
+        "--"
+    )
+    .dir(workspace_root)
+    .read()
+    .with_context(|| format!("failed to get diff against {base}"))?;
+    let mut diff = parse(&patch, &repository_root)?;
+    // `git diff` doesn't list files that have not been added to the index.
+    let untracked = cmd!("git", "ls-files", "--others", "--exclude-standard", "-z")
+        .dir(&repository_root)
+        .read()
+        .context("failed to list untracked files")?;
+    for path in untracked.split('\0').filter(|p| !p.is_empty()) {
+        let path = repository_root.join(path);
+        // Files removed after listing or unreadable files cannot be covered anyway.
+        let Ok(contents) = fs::read(&path) else { continue };
+        let lines = count_lines(&contents);
+        if lines != 0 {
+            diff.new_lines.insert(path, (1..=lines).collect());
+        }
+    }
+    Ok(diff)
+}
+
+/// Returns the number of lines of the given file contents.
+fn count_lines(contents: &[u8]) -> u64 {
+    let newlines = contents.iter().filter(|&&b| b == b'\n').count() as u64;
+    if contents.last().is_none_or(|&b| b == b'\n') { newlines } else { newlines + 1 }
+}
+
+/// Collects lines changed by the given unified diff file.
+///
+/// File paths in the diff are resolved relative to the root of the git repository
+/// (or the workspace root if the workspace is not in a git repository).
+pub(crate) fn from_file(workspace_root: &Utf8Path, path: &Utf8Path) -> Result<Diff> {
+    let repository_root =
+        repository_root(workspace_root).unwrap_or_else(|_| workspace_root.to_owned());
+    let patch = fs::read_to_string(path)?;
+    parse(&patch, &repository_root).with_context(|| format!("failed to parse diff file {path}"))
+}
+
+fn repository_root(workspace_root: &Utf8Path) -> Result<Utf8PathBuf> {
+    Ok(cmd!("git", "rev-parse", "--show-toplevel").dir(workspace_root).read()?.into())
+}
+
+/// Parses unified diff and collects lines changed in the old and new files.
+fn parse(patch: &str, root: &Utf8Path) -> Result<Diff> {
+    let mut diff = Diff::default();
+    // `None` if the file has been added.
+    let mut old: Option<Utf8PathBuf> = None;
+    // `None` until the first file header, `Some(None)` if the current file has been deleted.
+    let mut current: Option<Option<Utf8PathBuf>> = None;
+    let mut lines = patch.lines();
+    while let Some(line) = lines.next() {
+        if let Some(path) = line.strip_prefix("--- ") {
+            old = file_path(path, "a/").map(|path| root.join(path));
+            continue;
+        }
+        if let Some(path) = line.strip_prefix("+++ ") {
+            current = Some(file_path(path, "b/").map(|path| root.join(path)));
+            continue;
+        }
+        let Some(hunk) = line.strip_prefix("@@ ") else { continue };
+        let Some(current) = &current else { bail!("hunk without file header: {line}") };
+        let (mut old_line, mut old_remaining, mut new_line, mut new_remaining) =
+            parse_hunk_header(hunk).with_context(|| format!("invalid hunk header: {line}"))?;
+        if old_remaining == 0 {
+            // Lines are only added after `old_line`, so treat the surrounding lines as changed.
+            if let Some(path) = &old {
+                let lines = diff.old_lines.entry(path.clone()).or_default();
+                lines.extend([old_line, old_line + 1].into_iter().filter(|&l| l != 0));
+            }
+        }
+        while old_remaining != 0 || new_remaining != 0 {
+            let Some(line) = lines.next() else { bail!("unexpected end of hunk") };
+            match line.as_bytes().first() {
+                Some(b'+') => {
+                    if let Some(path) = current {
+                        diff.new_lines.entry(path.clone()).or_default().insert(new_line);
+                    }
+                    new_line += 1;
+                    new_remaining = new_remaining.saturating_sub(1);
+                }
+                Some(b'-') => {
+                    if let Some(path) = &old {
+                        diff.old_lines.entry(path.clone()).or_default().insert(old_line);
+                    }
+                    old_line += 1;
+                    old_remaining = old_remaining.saturating_sub(1);
+                }
+                // "\ No newline at end of file"
+                Some(b'\\') => {}
+                // Context line. Some tools strip trailing whitespaces, so empty line is also
+                // treated as context line.
+                Some(b' ') | None => {
+                    old_line += 1;
+                    new_line += 1;
+                    old_remaining = old_remaining.saturating_sub(1);
+                    new_remaining = new_remaining.saturating_sub(1);
+                }
+                Some(_) => bail!("unexpected line in hunk: {line}"),
+            }
+        }
+    }
+    Ok(diff)
+}
+
+/// Parses path from `--- <path>` or `+++ <path>` line.
+///
+/// Returns `None` if the file has been added (`---`) or deleted (`+++`).
+fn file_path(path: &str, prefix: &str) -> Option<String> {
+    let path = match path.strip_prefix('"') {
+        Some(quoted) => unquote(quoted),
+        // Some tools add timestamp after tab.
+        None => path.split('\t').next().unwrap().to_owned(),
+    };
+    if path == "/dev/null" {
+        return None;
+    }
+    Some(path.strip_prefix(prefix).map_or_else(|| path.clone(), str::to_owned))
+}
+
+/// Unescapes a path quoted by git (e.g., `"a\tb"` or `"\303\251.rs"`), given without the
+/// opening quote. Characters after the closing quote are ignored.
+///
+/// See the description of core.quotePath in https://git-scm.com/docs/git-config
+fn unquote(quoted: &str) -> String {
+    let mut bytes = Vec::with_capacity(quoted.len());
+    let mut iter = quoted.bytes().peekable();
+    while let Some(b) = iter.next() {
+        if b == b'"' {
+            break;
+        }
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        let Some(escaped) = iter.next() else {
+            bytes.push(b);
+            break;
+        };
+        bytes.push(match escaped {
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b't' => b'\t',
+            b'n' => b'\n',
+            b'v' => 0x0B,
+            b'f' => 0x0C,
+            b'r' => b'\r',
+            // Bytes other than printable ASCII are escaped as three octal digits.
+            b'0'..=b'3' => {
+                let mut byte = escaped - b'0';
+                for _ in 0..2 {
+                    let Some(d) = iter.next_if(|d| matches!(d, b'0'..=b'7')) else { break };
+                    byte = byte * 8 + (d - b'0');
+                }
+                byte
+            }
+            // `"` and `\`
+            _ => escaped,
+        });
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Parses `-<start>[,<count>] +<start>[,<count>] @@` and returns
+/// (start of old lines, number of old lines, start of new lines, number of new lines).
+fn parse_hunk_header(hunk: &str) -> Option<(u64, u64, u64, u64)> {
+    fn range(s: &str) -> Option<(u64, u64)> {
+        match s.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((s.parse().ok()?, 1)),
+        }
+    }
+    let mut iter = hunk.split(' ');
+    let (old_start, old_count) = range(iter.next()?.strip_prefix('-')?)?;
+    let (new_start, new_count) = range(iter.next()?.strip_prefix('+')?)?;
+    Some((old_start, old_count, new_start, new_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_diff() {
+        let patch = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -3,0 +4,2 @@ fn a() {
++    b();
++    c();
+@@ -10 +12 @@ fn d() {
+-    e();
++    f();
+@@ -20,3 +22,4 @@
+ fn g() {
+-    h();
++    i();
+ }
++++ not a header
+diff --git a/src/removed.rs b/src/removed.rs
+deleted file mode 100644
+--- a/src/removed.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-fn j() {}
+diff --git a/src/added.rs b/src/added.rs
+new file mode 100644
+--- /dev/null
++++ b/src/added.rs
+@@ -0,0 +1,2 @@
++fn k() {}
++fn l() {}
+\\ No newline at end of file
+";
+        let root = Utf8Path::new("/root");
+        let diff = parse(patch, root).unwrap();
+        let expected: ChangedLines = [
+            (root.join("src/lib.rs"), [4, 5, 12, 23, 25].into_iter().collect()),
+            (root.join("src/added.rs"), [1, 2].into_iter().collect()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(diff.new_lines, expected);
+        let expected: ChangedLines = [
+            (root.join("src/lib.rs"), [3, 4, 10, 21].into_iter().collect()),
+            (root.join("src/removed.rs"), [1].into_iter().collect()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(diff.old_lines, expected);
+    }
+
+    #[test]
+    fn quoted_path() {
+        let patch = "\
+diff --git \"a/src/\\303\\251\\t.rs\" \"b/src/\\303\\251\\t.rs\"
+--- \"a/src/\\303\\251\\t.rs\"
++++ \"b/src/\\303\\251\\t.rs\"
+@@ -1 +1 @@
+-fn a() {}
++fn b() {}
+";
+        let root = Utf8Path::new("/root");
+        let diff = parse(patch, root).unwrap();
+        let expected: ChangedLines =
+            [(root.join("src/\u{e9}\t.rs"), [1].into_iter().collect())].into_iter().collect();
+        assert_eq!(diff.new_lines, expected);
+        assert_eq!(diff.old_lines, expected);
+        assert_eq!(unquote("a\\\"b\\\\c\"\t2025-01-01"), "a\"b\\c");
+    }
+
+    #[test]
+    fn count_lines() {
+        assert_eq!(super::count_lines(b""), 0);
+        assert_eq!(super::count_lines(b"a"), 1);
+        assert_eq!(super::count_lines(b"a\n"), 1);
+        assert_eq!(super::count_lines(b"a\n\nb"), 3);
+    }
+}
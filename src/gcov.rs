@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Annotated source files in the text format of gcov, for gcovr and other tools that consume it.
+// Refs: https://gcc.gnu.org/onlinedocs/gcc/Invoking-Gcov.html
+
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use anyhow::Result;
+use camino::Utf8Path;
+use cargo_llvm_cov::json::{self, Export, File, LlvmCovJsonExport, RegionLocation};
+
+use crate::fs;
+
+/// Writes a `.gcov` file for each source file into `output_dir`.
+///
+/// Source files are read to annotate them. Files that cannot be read (e.g., removed after
+/// collecting coverage) are skipped with a warning.
+pub(crate) fn write(
+    json: &LlvmCovJsonExport,
+    workspace_root: &Utf8Path,
+    output_dir: &Utf8Path,
+) -> Result<()> {
+    for data in &json.data {
+        for file in &data.files {
+            // File names are relative to the workspace root if --remap-path-prefix is used.
+            let path = workspace_root.join(&file.filename);
+            let source = match fs::read(&path) {
+                Ok(source) => source,
+                Err(e) => {
+                    warn!("skipped {path} in gcov report: {e}");
+                    continue;
+                }
+            };
+            let out = to_string(data, file, &path, &String::from_utf8_lossy(&source));
+            fs::write(output_dir.join(file_name(workspace_root, &file.filename)), out)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the name of the `.gcov` file of the given source file, in the same way as
+/// `gcov --preserve-paths` (e.g., `src/lib.rs` -> `src#lib.rs.gcov`).
+fn file_name(workspace_root: &Utf8Path, filename: &str) -> String {
+    let path =
+        Utf8Path::new(filename).strip_prefix(workspace_root).map_or(filename, Utf8Path::as_str);
+    format!("{}.gcov", path.replace(['/', '\\', ':'], "#"))
+}
+
+fn to_string(data: &Export, file: &File, path: &Utf8Path, source: &str) -> String {
+    let line_counts: BTreeMap<u64, u64> = file.line_execution_counts().into_iter().collect();
+    let mut branches = file.branch_execution_counts().into_iter().peekable();
+    // Same as gcov, instantiations of the same function are merged.
+    // (start line, name) -> (execution count, location of region -> covered)
+    let mut functions: BTreeMap<(u64, String), (u64, BTreeMap<RegionLocation, bool>)> =
+        BTreeMap::new();
+    for f in data.functions_in(&file.filename) {
+        let name = json::demangle(f.name());
+        let function = functions.entry((f.start_line(), name)).or_default();
+        function.0 += f.count();
+        // Regions of macro expansions are in other files.
+        for (_, location, count) in f.code_regions().filter(|&(name, ..)| name == file.filename) {
+            *function.1.entry(location).or_default() |= count != 0;
+        }
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{:>9}:{:>5}:Source:{path}", "-", 0);
+    for (line, text) in (1..).zip(source.lines()) {
+        for ((_, name), (count, regions)) in
+            functions.range((line, String::new())..(line + 1, String::new()))
+        {
+            let covered = regions.values().filter(|&&covered| covered).count();
+            let blocks = if regions.is_empty() { 0 } else { covered * 100 / regions.len() };
+            // llvm-cov doesn't record whether the function returned, so assume that the
+            // function returned if it was called.
+            let returned = if *count == 0 { 0 } else { 100 };
+            let _ = writeln!(
+                out,
+                "function {name} called {count} returned {returned}% blocks executed {blocks}%"
+            );
+        }
+        let count = line_counts.get(&line).copied();
+        match count {
+            None => {
+                let _ = writeln!(out, "{:>9}:{line:>5}:{text}", "-");
+            }
+            Some(0) => {
+                let _ = writeln!(out, "{:>9}:{line:>5}:{text}", "#####");
+            }
+            Some(count) => {
+                let _ = writeln!(out, "{count:>9}:{line:>5}:{text}");
+            }
+        }
+        let mut n = 0;
+        while let Some(branch) = branches.next_if(|b| b.line == line) {
+            for taken in [branch.true_count, branch.false_count] {
+                if count.unwrap_or_default() == 0 {
+                    let _ = writeln!(out, "branch {n:>2} never executed");
+                } else {
+                    let _ = writeln!(out, "branch {n:>2} taken {taken}");
+                }
+                n += 1;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write as _;
+
+    use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+    use crate::fs;
+
+    #[test]
+    fn file_name() {
+        assert_eq!(super::file_name("/ws".into(), "/ws/src/lib.rs"), "src#lib.rs.gcov");
+        assert_eq!(super::file_name("/ws".into(), "src/lib.rs"), "src#lib.rs.gcov");
+        assert_eq!(super::file_name("/ws".into(), "/a/b.rs"), "#a#b.rs.gcov");
+    }
+
+    #[test]
+    fn gcov() {
+        let file = format!(
+            "{}/tests/fixtures/show-missing-lines-branches.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let json: LlvmCovJsonExport =
+            serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+        let data = &json.data[0];
+        let mut source = String::new();
+        for line in 1..=16 {
+            let _ = writeln!(source, "// {line}");
+        }
+        assert_eq!(
+            super::to_string(data, &data.files[0], "/ws/src/lib.rs".into(), &source),
+            "        -:    0:Source:/ws/src/lib.rs\n\
+             function t::main called 1 returned 100% blocks executed 100%\n\
+             \x20       1:    1:// 1\n\
+             \x20       -:    2:// 2\n\
+             function t::foo called 1 returned 100% blocks executed 50%\n\
+             \x20       1:    3:// 3\n\
+             \x20       1:    4:// 4\n\
+             branch  0 taken 1\n\
+             branch  1 taken 0\n\
+             \x20       1:    5:// 5\n\
+             \x20       -:    6:// 6\n\
+             function t::bar called 0 returned 0% blocks executed 0%\n\
+             \x20   #####:    7:// 7\n\
+             \x20   #####:    8:// 8\n\
+             \x20   #####:    9:// 9\n\
+             \x20       -:   10:// 10\n\
+             \x20       -:   11:// 11\n\
+             \x20       -:   12:// 12\n\
+             \x20       -:   13:// 13\n\
+             function t::tests::it_works::{closure#0} called 1 returned 100% blocks executed 100%\n\
+             \x20       1:   14:// 14\n\
+             function t::tests::it_works called 1 returned 100% blocks executed 100%\n\
+             \x20       1:   15:// 15\n\
+             \x20       1:   16:// 16\n"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Istanbul file coverage JSON (`coverage-final.json`).
+// Refs: https://github.com/istanbuljs/istanbuljs/blob/main/docs/raw-output.md
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use camino::Utf8Path;
+use cargo_llvm_cov::json::{self, LlvmCovJsonExport, RegionLocation};
+use serde::ser::{Serialize, Serializer};
+use serde_derive::Serialize;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileCoverage {
+    path: String,
+    statement_map: Indexed<Range>,
+    fn_map: Indexed<FunctionMapping>,
+    branch_map: Indexed<BranchMapping>,
+    /// Execution count of each statement.
+    s: Indexed<u64>,
+    /// Execution count of each function.
+    f: Indexed<u64>,
+    /// Execution counts of the true and false branches of each branch.
+    b: Indexed<[u64; 2]>,
+}
+
+#[derive(Clone, Copy, Serialize)]
+struct Range {
+    start: Position,
+    end: Position,
+}
+
+/// Lines are 1-based, and columns are 0-based.
+#[derive(Clone, Copy, Serialize)]
+struct Position {
+    line: u64,
+    column: u64,
+}
+
+#[derive(Serialize)]
+struct FunctionMapping {
+    name: String,
+    decl: Range,
+    loc: Range,
+    line: u64,
+}
+
+#[derive(Serialize)]
+struct BranchMapping {
+    loc: Range,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    locations: [Range; 2],
+    line: u64,
+}
+
+/// A list serialized as an object keyed by the index of each element (e.g., `{"0":..,"1":..}`).
+struct Indexed<T>(Vec<T>);
+
+impl<T: Serialize> Serialize for Indexed<T> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_map(self.0.iter().enumerate().map(|(i, v)| (i.to_string(), v)))
+    }
+}
+
+/// Returns the report in Istanbul JSON format.
+///
+/// Code regions, functions, and branches are reported as statements, functions, and branches,
+/// respectively. Execution counts of instantiations of the same function are summed up.
+pub(crate) fn to_string(json: &LlvmCovJsonExport, workspace_root: &Utf8Path) -> Result<String> {
+    let mut files = BTreeMap::new();
+    for data in &json.data {
+        for file in &data.files {
+            // location -> execution count
+            let mut statements: BTreeMap<RegionLocation, u64> = BTreeMap::new();
+            // location of the function body -> (name, execution count)
+            let mut functions: BTreeMap<RegionLocation, (&str, u64)> = BTreeMap::new();
+            // location -> [true count, false count]
+            let mut branches: BTreeMap<RegionLocation, [u64; 2]> = BTreeMap::new();
+            for f in data.functions_in(&file.filename) {
+                // Regions of macro expansions are in other files.
+                let mut code_regions = f.code_regions().filter(|&(name, ..)| name == file.filename);
+                let Some((_, body, count)) = code_regions.next() else { continue };
+                functions.entry(body).or_insert((f.name(), 0)).1 += f.count();
+                *statements.entry(body).or_default() += count;
+                for (_, location, count) in code_regions {
+                    *statements.entry(location).or_default() += count;
+                }
+                for (_, location, counts) in
+                    f.branch_regions().filter(|&(name, ..)| name == file.filename)
+                {
+                    let branch = branches.entry(location).or_default();
+                    branch[0] += counts.true_count;
+                    branch[1] += counts.false_count;
+                }
+            }
+
+            // Istanbul uses absolute paths, and the file names are relative to the workspace
+            // root if --remap-path-prefix is used.
+            let path = workspace_root.join(&file.filename).into_string();
+            let coverage = FileCoverage {
+                path: path.clone(),
+                statement_map: Indexed(statements.keys().copied().map(range).collect()),
+                fn_map: Indexed(
+                    functions
+                        .iter()
+                        .map(|(&location, &(name, _))| FunctionMapping {
+                            name: json::demangle(name),
+                            decl: range(location),
+                            loc: range(location),
+                            line: location.start_line,
+                        })
+                        .collect(),
+                ),
+                branch_map: Indexed(
+                    branches
+                        .keys()
+                        .map(|&location| BranchMapping {
+                            loc: range(location),
+                            kind: "if",
+                            locations: [range(location); 2],
+                            line: location.start_line,
+                        })
+                        .collect(),
+                ),
+                s: Indexed(statements.into_values().collect()),
+                f: Indexed(functions.into_values().map(|(_, count)| count).collect()),
+                b: Indexed(branches.into_values().collect()),
+            };
+            files.insert(path, coverage);
+        }
+    }
+    Ok(serde_json::to_string(&files)?)
+}
+
+fn range(location: RegionLocation) -> Range {
+    // Columns of llvm-cov are 1-based, and the end column is exclusive in both.
+    Range {
+        start: Position {
+            line: location.start_line,
+            column: location.start_column.saturating_sub(1),
+        },
+        end: Position { line: location.end_line, column: location.end_column.saturating_sub(1) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+    use crate::fs;
+
+    #[test]
+    fn istanbul() {
+        let file = format!(
+            "{}/tests/fixtures/show-missing-lines-branches.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let json: LlvmCovJsonExport =
+            serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+        let out: serde_json::Value =
+            serde_json::from_str(&super::to_string(&json, "/ws".into()).unwrap()).unwrap();
+        let file = &out["/ws/src/lib.rs"];
+        let range = |start_line, start_column, end_line, end_column| {
+            serde_json::json!({
+                "start": { "line": start_line, "column": start_column },
+                "end": { "line": end_line, "column": end_column },
+            })
+        };
+        assert_eq!(file["path"], "/ws/src/lib.rs");
+        assert_eq!(file["statementMap"]["4"], range(8, 12, 8, 13));
+        assert_eq!(
+            file["s"],
+            serde_json::json!({ "0": 1, "1": 1, "2": 0, "3": 0, "4": 0, "5": 0, "6": 1, "7": 1 })
+        );
+        assert_eq!(
+            file["fnMap"]["2"],
+            serde_json::json!({
+                "name": "t::bar",
+                "decl": range(7, 0, 8, 12),
+                "loc": range(7, 0, 8, 12),
+                "line": 7,
+            })
+        );
+        assert_eq!(file["f"], serde_json::json!({ "0": 1, "1": 1, "2": 0, "3": 1, "4": 1 }));
+        assert_eq!(
+            file["branchMap"],
+            serde_json::json!({
+                "0": {
+                    "loc": range(4, 7, 4, 12),
+                    "type": "if",
+                    "locations": [range(4, 7, 4, 12), range(4, 7, 4, 12)],
+                    "line": 4,
+                },
+            })
+        );
+        assert_eq!(file["b"], serde_json::json!({ "0": [1, 0] }));
+    }
+}
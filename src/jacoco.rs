@@ -0,0 +1,263 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// JaCoCo XML report.
+// Refs: https://www.jacoco.org/jacoco/trunk/coverage/report.dtd
+//
+// Each package is a crate and each source file is a file in it, so tools that resolve source
+// files as `<package>/<sourcefile>` find them if the crate is in a directory of the same name.
+// Functions are reported as methods of a class per source file because JaCoCo has no other way
+// to represent them.
+
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use camino::Utf8Path;
+use cargo_llvm_cov::json::{self, File, Function, LlvmCovJsonExport};
+
+use crate::xml;
+
+#[derive(Clone, Copy, Default)]
+struct Counter {
+    missed: u64,
+    covered: u64,
+}
+
+impl Counter {
+    fn add(&mut self, covered: bool, n: u64) {
+        if covered {
+            self.covered += n;
+        } else {
+            self.missed += n;
+        }
+    }
+}
+
+/// LINE, BRANCH, and METHOD counters.
+#[derive(Clone, Copy, Default)]
+struct Counters([Counter; 3]);
+
+impl Counters {
+    const TYPES: [&'static str; 3] = ["LINE", "BRANCH", "METHOD"];
+
+    fn add(&mut self, other: &Self) {
+        for (c, o) in self.0.iter_mut().zip(other.0) {
+            c.missed += o.missed;
+            c.covered += o.covered;
+        }
+    }
+
+    fn write(&self, out: &mut String, indent: &str) {
+        for (ty, c) in Self::TYPES.iter().zip(self.0) {
+            // Same as JaCoCo, counters of elements that have nothing to cover are omitted.
+            if c.missed + c.covered != 0 {
+                let _ = writeln!(
+                    out,
+                    "{indent}<counter type=\"{ty}\" missed=\"{}\" covered=\"{}\"/>",
+                    c.missed, c.covered
+                );
+            }
+        }
+    }
+}
+
+struct SourceFile<'a> {
+    /// Path relative to the package directory, or file name if not in any package.
+    name: String,
+    file: &'a File,
+    functions: Vec<&'a Function>,
+}
+
+/// Returns the report in JaCoCo XML format.
+///
+/// `package_of` returns the name and the directory of the package that contains the given file.
+/// Files that are not in any package are grouped by the directory that contains them.
+pub(crate) fn to_string<'a>(
+    json: &'a LlvmCovJsonExport,
+    workspace_root: &Utf8Path,
+    package_of: impl Fn(&str) -> Option<(&'a str, &'a Utf8Path)>,
+) -> String {
+    let mut packages: BTreeMap<&str, Vec<SourceFile<'_>>> = BTreeMap::new();
+    for data in &json.data {
+        for file in &data.files {
+            let (package, name) = match package_of(&file.filename) {
+                Some((package, dir)) => {
+                    // File names are relative to the workspace root if --remap-path-prefix is
+                    // used.
+                    let path = workspace_root.join(&file.filename);
+                    (package, path.strip_prefix(dir).unwrap_or(&path).as_str().to_owned())
+                }
+                None => {
+                    let path = Utf8Path::new(&file.filename);
+                    (
+                        path.parent().map_or("", Utf8Path::as_str),
+                        path.file_name().unwrap_or(&file.filename).to_owned(),
+                    )
+                }
+            };
+            let functions = data.functions_in(&file.filename).collect();
+            packages.entry(package).or_default().push(SourceFile { name, file, functions });
+        }
+    }
+
+    let mut out = String::new();
+    out.push_str(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+         <!DOCTYPE report PUBLIC \"-//JACOCO//DTD Report 1.1//EN\" \"report.dtd\">\n",
+    );
+    let report_name = workspace_root.file_name().unwrap_or_default();
+    let _ = writeln!(out, "<report name=\"{}\">", xml::escape(report_name));
+    let mut report_counters = Counters::default();
+    for (package, files) in packages {
+        let _ = writeln!(out, "  <package name=\"{}\">", xml::escape(package));
+        let mut package_counters = Counters::default();
+        for file in &files {
+            write_class(&mut out, package, file);
+        }
+        for file in &files {
+            package_counters.add(&write_source_file(&mut out, file));
+        }
+        package_counters.write(&mut out, "    ");
+        out.push_str("  </package>\n");
+        report_counters.add(&package_counters);
+    }
+    report_counters.write(&mut out, "  ");
+    out.push_str("</report>\n");
+    out
+}
+
+fn write_class(out: &mut String, package: &str, file: &SourceFile<'_>) {
+    // Instantiations of the same generic function are merged.
+    let mut methods: BTreeMap<(u64, String), u64> = BTreeMap::new();
+    for f in &file.functions {
+        *methods.entry((f.start_line(), json::demangle(f.name()))).or_default() += f.count();
+    }
+    if methods.is_empty() {
+        return;
+    }
+    let class = format!("{package}/{}", file.name.strip_suffix(".rs").unwrap_or(&file.name));
+    let _ = writeln!(
+        out,
+        "    <class name=\"{}\" sourcefilename=\"{}\">",
+        xml::escape(&class),
+        xml::escape(&file.name)
+    );
+    let mut class_counters = Counters::default();
+    for ((line, name), count) in methods {
+        let mut counters = Counters::default();
+        counters.0[2].add(count != 0, 1);
+        // `desc` is the JVM method descriptor, which is required but has no equivalent in Rust.
+        // Use the descriptor of a method without parameters and return value for all functions.
+        let _ = writeln!(
+            out,
+            "      <method name=\"{}\" desc=\"()V\" line=\"{line}\">",
+            xml::escape(&name)
+        );
+        counters.write(out, "        ");
+        out.push_str("      </method>\n");
+        class_counters.add(&counters);
+    }
+    class_counters.write(out, "      ");
+    out.push_str("    </class>\n");
+}
+
+fn write_source_file(out: &mut String, file: &SourceFile<'_>) -> Counters {
+    let mut counters = Counters::default();
+    let _ = writeln!(out, "    <sourcefile name=\"{}\">", xml::escape(&file.name));
+    for (line, counts) in file.file.line_branch_counts() {
+        let covered = counts.count != 0;
+        let covered_branches = counts.covered_branches;
+        let missed_branches = counts.branches - covered_branches;
+        let _ = writeln!(
+            out,
+            "      <line nr=\"{line}\" mi=\"{}\" ci=\"{}\" mb=\"{missed_branches}\" \
+             cb=\"{covered_branches}\"/>",
+            u64::from(!covered),
+            u64::from(covered)
+        );
+        counters.0[0].add(covered, 1);
+        counters.0[1].add(false, missed_branches);
+        counters.0[1].add(true, covered_branches);
+    }
+    // Use the summary of llvm-cov so that the number of functions is consistent with other
+    // reports.
+    let functions = &file.file.summary.functions;
+    counters.0[2].add(true, functions.covered);
+    counters.0[2].add(false, functions.count.saturating_sub(functions.covered));
+    counters.write(out, "      ");
+    out.push_str("    </sourcefile>\n");
+    counters
+}
+
+#[cfg(test)]
+mod tests {
+    use camino::Utf8Path;
+    use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+    use crate::fs;
+
+    #[test]
+    fn jacoco() {
+        let file = format!(
+            "{}/tests/fixtures/show-missing-lines-branches.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+        json["data"][0]["files"][0]["filename"] = "/ws/t/src/lib.rs".into();
+        for f in json["data"][0]["functions"].as_array_mut().unwrap() {
+            f["filenames"][0] = "/ws/t/src/lib.rs".into();
+        }
+        let json: LlvmCovJsonExport = serde_json::from_value(json).unwrap();
+        let out = super::to_string(&json, "/ws".into(), |f| {
+            f.starts_with("/ws/t/").then_some(("t", Utf8Path::new("/ws/t")))
+        });
+        assert_eq!(
+            out,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+             <!DOCTYPE report PUBLIC \"-//JACOCO//DTD Report 1.1//EN\" \"report.dtd\">\n\
+             <report name=\"ws\">\n  \
+             <package name=\"t\">\n    \
+             <class name=\"t/src/lib\" sourcefilename=\"src/lib.rs\">\n      \
+             <method name=\"t::main\" desc=\"()V\" line=\"1\">\n        \
+             <counter type=\"METHOD\" missed=\"0\" covered=\"1\"/>\n      \
+             </method>\n      \
+             <method name=\"t::foo\" desc=\"()V\" line=\"3\">\n        \
+             <counter type=\"METHOD\" missed=\"0\" covered=\"1\"/>\n      \
+             </method>\n      \
+             <method name=\"t::bar\" desc=\"()V\" line=\"7\">\n        \
+             <counter type=\"METHOD\" missed=\"1\" covered=\"0\"/>\n      \
+             </method>\n      \
+             <method name=\"t::tests::it_works::{closure#0}\" desc=\"()V\" line=\"14\">\n        \
+             <counter type=\"METHOD\" missed=\"0\" covered=\"1\"/>\n      \
+             </method>\n      \
+             <method name=\"t::tests::it_works\" desc=\"()V\" line=\"15\">\n        \
+             <counter type=\"METHOD\" missed=\"0\" covered=\"1\"/>\n      \
+             </method>\n      \
+             <counter type=\"METHOD\" missed=\"1\" covered=\"4\"/>\n    \
+             </class>\n    \
+             <sourcefile name=\"src/lib.rs\">\n      \
+             <line nr=\"1\" mi=\"0\" ci=\"1\" mb=\"0\" cb=\"0\"/>\n      \
+             <line nr=\"3\" mi=\"0\" ci=\"1\" mb=\"0\" cb=\"0\"/>\n      \
+             <line nr=\"4\" mi=\"0\" ci=\"1\" mb=\"1\" cb=\"1\"/>\n      \
+             <line nr=\"5\" mi=\"0\" ci=\"1\" mb=\"0\" cb=\"0\"/>\n      \
+             <line nr=\"7\" mi=\"1\" ci=\"0\" mb=\"0\" cb=\"0\"/>\n      \
+             <line nr=\"8\" mi=\"1\" ci=\"0\" mb=\"0\" cb=\"0\"/>\n      \
+             <line nr=\"9\" mi=\"1\" ci=\"0\" mb=\"0\" cb=\"0\"/>\n      \
+             <line nr=\"14\" mi=\"0\" ci=\"1\" mb=\"0\" cb=\"0\"/>\n      \
+             <line nr=\"15\" mi=\"0\" ci=\"1\" mb=\"0\" cb=\"0\"/>\n      \
+             <line nr=\"16\" mi=\"0\" ci=\"1\" mb=\"0\" cb=\"0\"/>\n      \
+             <line nr=\"17\" mi=\"0\" ci=\"1\" mb=\"0\" cb=\"0\"/>\n      \
+             <counter type=\"LINE\" missed=\"3\" covered=\"8\"/>\n      \
+             <counter type=\"BRANCH\" missed=\"1\" covered=\"1\"/>\n      \
+             <counter type=\"METHOD\" missed=\"1\" covered=\"4\"/>\n    \
+             </sourcefile>\n    \
+             <counter type=\"LINE\" missed=\"3\" covered=\"8\"/>\n    \
+             <counter type=\"BRANCH\" missed=\"1\" covered=\"1\"/>\n    \
+             <counter type=\"METHOD\" missed=\"1\" covered=\"4\"/>\n  \
+             </package>\n  \
+             <counter type=\"LINE\" missed=\"3\" covered=\"8\"/>\n  \
+             <counter type=\"BRANCH\" missed=\"1\" covered=\"1\"/>\n  \
+             <counter type=\"METHOD\" missed=\"1\" covered=\"4\"/>\n\
+             </report>\n"
+        );
+    }
+}
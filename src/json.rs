@@ -4,17 +4,19 @@
 // TODO: reflect https://github.com/llvm/llvm-project/commit/8ecbb0404d740d1ab173554e47cef39cd5e3ef8c#diff-e5de2b538138d03e13b43901f61adc61992516c742991ebaf1a13f2f8623910a?
 
 use std::{
-    collections::{BTreeMap, HashMap},
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap},
     fmt,
+    str::FromStr,
 };
 
-use anyhow::{Context as _, Result};
+use anyhow::{Context as _, Result, bail};
 use camino::Utf8PathBuf;
 use regex::Regex;
 use serde::ser::{Serialize, SerializeMap as _, Serializer};
 use serde_derive::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
 pub struct LlvmCovJsonExport {
     /// List of one or more export objects
@@ -72,21 +74,21 @@ pub struct CodeCovJsonExport {
 }
 
 impl CodeCovJsonExport {
-    fn from_export(value: Export, ignore_filename_regex: Option<&Regex>) -> Self {
-        let functions = value.functions.unwrap_or_default();
+    fn from_export(value: &Export, ignore_filename_regex: Option<&Regex>) -> Self {
+        let functions = value.functions.as_deref().unwrap_or_default();
 
         let mut regions = HashMap::new();
 
         for func in functions {
-            for filename in func.filenames {
+            for filename in &func.filenames {
                 if let Some(re) = ignore_filename_regex {
-                    if re.is_match(&filename) {
+                    if re.is_match(filename) {
                         continue;
                     }
                 }
                 // region location to covered
                 let coverage: &mut HashMap<RegionLocation, bool> =
-                    regions.entry(filename).or_default();
+                    regions.entry(filename.clone()).or_default();
                 for region in &func.regions {
                     let loc = RegionLocation::from(region);
 
@@ -100,10 +102,14 @@ impl CodeCovJsonExport {
         let mut coverage = BTreeMap::new();
 
         for (filename, regions) in regions {
+            let excluded = value.excluded_lines.get(&filename);
             let coverage: &mut CodeCovExport = coverage.entry(filename).or_default();
 
             for (loc, covered) in regions {
                 for line in loc.lines() {
+                    if excluded.is_some_and(|excluded| excluded.contains(&line)) {
+                        continue;
+                    }
                     let coverage = coverage.0.entry(line).or_default();
                     coverage.count += 1;
                     coverage.covered += covered as u64;
@@ -111,16 +117,56 @@ impl CodeCovJsonExport {
             }
         }
 
+        // Files added by add_unreported_files have no functions.
+        for file in value.files.iter().filter(|f| value.unreported_files.contains(&f.filename)) {
+            if ignore_filename_regex.is_some_and(|re| re.is_match(&file.filename)) {
+                continue;
+            }
+            let coverage: &mut CodeCovExport = coverage.entry(file.filename.clone()).or_default();
+            for (line, _) in file.line_execution_counts() {
+                coverage.0.insert(line, CodeCovCoverage { count: 1, covered: 0 });
+            }
+        }
+
+        // Lines that have branches are reported with the number of taken branches instead of
+        // covered regions, so that Codecov shows lines whose branches have not all been taken
+        // as partial.
+        for file in &value.files {
+            if ignore_filename_regex.is_some_and(|re| re.is_match(&file.filename)) {
+                continue;
+            }
+            let branches = file.branch_execution_counts();
+            if branches.is_empty() {
+                continue;
+            }
+            let mut lines: BTreeMap<u64, CodeCovCoverage> = BTreeMap::new();
+            for branch in branches {
+                let line = lines.entry(branch.line).or_default();
+                line.count += 2;
+                line.covered +=
+                    u64::from(branch.true_count != 0) + u64::from(branch.false_count != 0);
+            }
+            let coverage: &mut CodeCovExport = coverage.entry(file.filename.clone()).or_default();
+            coverage.0.extend(lines);
+        }
+
         Self { coverage }
     }
 
+    #[allow(clippy::needless_pass_by_value)] // Kept for compatibility.
     #[must_use]
     pub fn from_llvm_cov_json_export(
         value: LlvmCovJsonExport,
         ignore_filename_regex: Option<&str>,
     ) -> Self {
+        Self::new(&value, ignore_filename_regex)
+    }
+
+    /// Same as [`Self::from_llvm_cov_json_export`], but doesn't take ownership of `value`.
+    #[must_use]
+    pub fn new(value: &LlvmCovJsonExport, ignore_filename_regex: Option<&str>) -> Self {
         let re = ignore_filename_regex.map(|s| Regex::new(s).unwrap());
-        let exports = value.data.into_iter().map(|v| Self::from_export(v, re.as_ref()));
+        let exports = value.data.iter().map(|v| Self::from_export(v, re.as_ref()));
 
         let mut combined = CodeCovJsonExport::default();
 
@@ -146,31 +192,61 @@ impl CodeCovJsonExport {
 /// Files -> list of uncovered lines.
 pub type UncoveredLines = BTreeMap<String, Vec<u64>>;
 
+/// Files -> list of uncovered code regions.
+pub type UncoveredRegions = BTreeMap<String, Vec<RegionLocation>>;
+
+/// Files -> line -> execution count.
+pub type LineCounts = BTreeMap<String, BTreeMap<u64, u64>>;
+
 #[non_exhaustive]
-#[derive(Clone, Copy)]
-#[cfg_attr(test, derive(Debug))]
+#[derive(Debug, Clone, Copy)]
 pub enum CoverageKind {
     Functions,
     Lines,
     Regions,
+    Branches,
 }
 
 impl CoverageKind {
-    fn as_str(self) -> &'static str {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
         match self {
             Self::Functions => "functions",
             Self::Lines => "lines",
             Self::Regions => "regions",
+            Self::Branches => "branches",
         }
     }
 }
 
+impl FromStr for CoverageKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "functions" => Ok(Self::Functions),
+            "lines" => Ok(Self::Lines),
+            "regions" => Ok(Self::Regions),
+            "branches" => Ok(Self::Branches),
+            _ => bail!(
+                "unrecognized coverage kind '{s}'; possible values: functions, lines, regions, branches"
+            ),
+        }
+    }
+}
+
+/// Demangles the given symbol name of a function, without the hash.
+#[must_use]
+pub fn demangle(name: &str) -> String {
+    format!("{:#}", rustc_demangle::demangle(name))
+}
+
 impl LlvmCovJsonExport {
     pub fn demangle(&mut self) {
         for data in &mut self.data {
             if let Some(functions) = &mut data.functions {
                 for func in functions {
-                    func.name = format!("{:#}", rustc_demangle::demangle(&func.name));
+                    func.name = demangle(&func.name);
                 }
             }
         }
@@ -183,6 +259,88 @@ impl LlvmCovJsonExport {
         });
     }
 
+    /// Removes files for which `f` returns `false`, and updates totals accordingly.
+    ///
+    /// Same as `-ignore-filename-regex` flag of `llvm-cov export`, this doesn't remove functions.
+    pub fn retain_files(&mut self, mut f: impl FnMut(&str) -> bool) {
+        for data in &mut self.data {
+            let len = data.files.len();
+            data.files.retain(|file| f(&file.filename));
+            if data.files.len() != len {
+                data.totals = totals(&data.files);
+            }
+        }
+    }
+
+    /// Removes information that is omitted by `-summary-only` or `-skip-functions` flag of
+    /// `llvm-cov export`.
+    pub fn remove_details(&mut self, summary_only: bool, skip_functions: bool) {
+        for data in &mut self.data {
+            if summary_only || skip_functions {
+                data.functions = None;
+            }
+            if summary_only {
+                for file in &mut data.files {
+                    file.branches = None;
+                    file.mcdc_records = None;
+                    file.expansions = None;
+                    file.segments = None;
+                }
+            }
+        }
+    }
+
+    /// Removes the given lines (file name -> lines) from segments, regions, and branches, and
+    /// updates summaries and totals accordingly.
+    ///
+    /// Regions that are only partially in the given lines are kept, but the given lines are not
+    /// counted as lines of them. Functions that have no regions left in their own file are
+    /// removed.
+    ///
+    /// This requires details that are omitted by `-summary-only` flag of `llvm-cov export`.
+    pub fn exclude_lines(&mut self, excluded_lines: &BTreeMap<String, BTreeSet<u64>>) {
+        for data in &mut self.data {
+            let mut changed = false;
+            for file in &mut data.files {
+                let Some(lines) = excluded_lines.get(&file.filename).filter(|l| !l.is_empty())
+                else {
+                    continue;
+                };
+                changed = true;
+                file.exclude_lines(lines);
+                if let Some(functions) = &mut data.functions {
+                    file.summary.exclude_functions(functions, &file.filename, lines);
+                }
+                data.excluded_lines.insert(file.filename.clone(), lines.clone());
+            }
+            if !changed {
+                continue;
+            }
+            if let Some(functions) = &mut data.functions {
+                functions.retain(|f| {
+                    !excluded_lines.contains_key(f.filenames.first().map_or("", String::as_str))
+                        || f.regions.iter().any(|r| r.file_id() == 0)
+                });
+            }
+            data.totals = totals(&data.files);
+        }
+    }
+
+    /// Adds files that are not in the export (e.g., because they are never compiled) as files
+    /// whose given lines (file name -> lines) are not covered, and updates totals accordingly.
+    pub fn add_unreported_files(&mut self, files: BTreeMap<String, Vec<u64>>) {
+        let Some(data) = self.data.first_mut() else { return };
+        if files.is_empty() {
+            return;
+        }
+        for (filename, lines) in files {
+            data.unreported_files.insert(filename.clone());
+            data.files.push(File::uncovered(filename, &lines));
+        }
+        data.files.sort_by(|a, b| a.filename.cmp(&b.filename));
+        data.totals = totals(&data.files);
+    }
+
     /// Gets the minimal lines coverage of all files.
     pub fn get_coverage_percent(&self, kind: CoverageKind) -> Result<f64> {
         let mut count = 0_f64;
@@ -211,11 +369,13 @@ impl LlvmCovJsonExport {
             .all(|file| file.summary.lines.percent > threshold)
     }
 
-    /// Gets the list of uncovered lines of all files.
+    /// Gets the execution count of each line of all files.
+    ///
+    /// The count of a line is the sum of the execution counts of the regions that contain that
+    /// line, in all functions that contain that line.
     #[must_use]
-    pub fn get_uncovered_lines(&self, ignore_filename_regex: Option<&str>) -> UncoveredLines {
-        let mut uncovered_files: UncoveredLines = BTreeMap::new();
-        let mut covered_files: UncoveredLines = BTreeMap::new();
+    pub fn get_line_execution_counts(&self, ignore_filename_regex: Option<&str>) -> LineCounts {
+        let mut files: LineCounts = BTreeMap::new();
         let re = ignore_filename_regex.map(|s| Regex::new(s).unwrap());
         for data in &self.data {
             if let Some(ref functions) = data.functions {
@@ -243,48 +403,88 @@ impl LlvmCovJsonExport {
                         }
                     }
 
-                    let mut uncovered_lines: Vec<u64> = lines
-                        .iter()
-                        .filter(|(_line, exec_count)| **exec_count == 0)
-                        .map(|(line, _exec_count)| *line)
-                        .collect();
-                    let mut covered_lines: Vec<u64> = lines
-                        .iter()
-                        .filter(|(_line, exec_count)| **exec_count > 0)
-                        .map(|(line, _exec_count)| *line)
-                        .collect();
-                    if !uncovered_lines.is_empty() {
-                        uncovered_files
-                            .entry(file_name.clone())
-                            .or_default()
-                            .append(&mut uncovered_lines);
-                    }
-                    if !covered_lines.is_empty() {
-                        covered_files
-                            .entry(file_name.clone())
-                            .or_default()
-                            .append(&mut covered_lines);
+                    // A line is covered if it is covered in any of the functions.
+                    let excluded = data.excluded_lines.get(file_name);
+                    let file_lines = files.entry(file_name.clone()).or_default();
+                    for (line, exec_count) in lines {
+                        if excluded.is_some_and(|excluded| excluded.contains(&line)) {
+                            continue;
+                        }
+                        *file_lines.entry(line).or_insert(0) += exec_count;
                     }
                 }
             }
+            // Files added by add_unreported_files have no functions.
+            for file in data.files.iter().filter(|f| data.unreported_files.contains(&f.filename)) {
+                if re.as_ref().is_some_and(|re| re.is_match(&file.filename)) {
+                    continue;
+                }
+                files
+                    .entry(file.filename.clone())
+                    .or_default()
+                    .extend(file.line_execution_counts());
+            }
         }
+        files
+    }
 
-        for uncovered_file in &mut uncovered_files {
-            // Check if a line is both covered and non-covered. It's covered in this case.
-            let file_name = uncovered_file.0;
-            let uncovered_lines = uncovered_file.1;
-            if let Some(covered_lines) = covered_files.get(file_name) {
-                uncovered_lines.retain(|&x| !covered_lines.contains(&x));
+    /// Gets the list of uncovered lines of all files.
+    #[must_use]
+    pub fn get_uncovered_lines(&self, ignore_filename_regex: Option<&str>) -> UncoveredLines {
+        let mut uncovered_files: UncoveredLines = BTreeMap::new();
+        for (file_name, lines) in self.get_line_execution_counts(ignore_filename_regex) {
+            let uncovered_lines: Vec<u64> = lines
+                .iter()
+                .filter(|(_line, exec_count)| **exec_count == 0)
+                .map(|(line, _exec_count)| *line)
+                .collect();
+            // Remove empty keys.
+            if !uncovered_lines.is_empty() {
+                uncovered_files.insert(file_name, uncovered_lines);
             }
-
-            // Remove duplicates.
-            uncovered_lines.sort_unstable();
-            uncovered_lines.dedup();
         }
+        uncovered_files
+    }
 
-        // Remove empty keys.
-        uncovered_files.retain(|_, v| !v.is_empty());
+    /// Gets the list of uncovered code regions of all files.
+    ///
+    /// A region is covered if it is covered in any instantiation of the function. Regions
+    /// nested in another uncovered region are omitted.
+    #[must_use]
+    pub fn get_uncovered_regions(&self, ignore_filename_regex: Option<&str>) -> UncoveredRegions {
+        let re = ignore_filename_regex.map(|s| Regex::new(s).unwrap());
+        // file name -> location -> covered
+        let mut files: BTreeMap<&str, BTreeMap<RegionLocation, bool>> = BTreeMap::new();
+        for function in self.data.iter().filter_map(|data| data.functions.as_ref()).flatten() {
+            for (file_name, location, count) in function.code_regions() {
+                if re.as_ref().is_some_and(|re| re.is_match(file_name)) {
+                    continue;
+                }
+                *files.entry(file_name).or_default().entry(location).or_default() |= count != 0;
+            }
+        }
 
+        let mut uncovered_files: UncoveredRegions = BTreeMap::new();
+        for (file_name, regions) in files {
+            let mut uncovered_regions: Vec<RegionLocation> =
+                regions.into_iter().filter(|&(_, covered)| !covered).map(|(r, _)| r).collect();
+            // Sort by start position, and place outer regions before regions nested in them.
+            uncovered_regions.sort_by_key(|r| {
+                (r.start_line, r.start_column, Reverse(r.end_line), Reverse(r.end_column))
+            });
+            let mut outer: Option<RegionLocation> = None;
+            uncovered_regions.retain(|r| {
+                if outer.is_some_and(|o| (o.end_line, o.end_column) >= (r.end_line, r.end_column)) {
+                    return false;
+                }
+                outer = Some(*r);
+                true
+            });
+            // Remove empty keys.
+            if !uncovered_regions.is_empty() {
+                uncovered_files.insert(file_name.to_owned(), uncovered_regions);
+            }
+        }
         uncovered_files
     }
 
@@ -326,7 +526,7 @@ impl LlvmCovJsonExport {
 }
 
 /// Json representation of one `CoverageMapping`
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
 pub struct Export {
     /// List of objects describing coverage for files
@@ -337,10 +537,44 @@ pub struct Export {
     #[serde(skip_serializing_if = "Option::is_none")]
     functions: Option<Vec<Function>>,
     totals: serde_json::Value,
+    /// Lines removed by [`LlvmCovJsonExport::exclude_lines`] (file name -> lines).
+    #[serde(skip)]
+    excluded_lines: BTreeMap<String, BTreeSet<u64>>,
+    /// Files added by [`LlvmCovJsonExport::add_unreported_files`].
+    #[serde(skip)]
+    unreported_files: BTreeSet<String>,
+}
+
+impl Export {
+    /// Returns functions whose main source file is `filename`, in the same order as `llvm-cov`.
+    pub fn functions_in<'a>(&'a self, filename: &'a str) -> impl Iterator<Item = &'a Function> {
+        self.functions.iter().flatten().filter(move |f| f.is_in(filename))
+    }
+}
+
+/// Computes totals of the given files in the same format as `llvm-cov export`.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn totals(files: &[File]) -> serde_json::Value {
+    let mut totals = Summary::default();
+    totals.branches.notcovered = Some(0);
+    totals.regions.notcovered = Some(0);
+    for file in files {
+        totals.add(&file.summary);
+    }
+    let mut totals = serde_json::to_value(totals).unwrap();
+    // llvm-cov writes integral percentages without fractional part.
+    for counts in totals.as_object_mut().unwrap().values_mut() {
+        if let Some(percent) = counts.get_mut("percent") {
+            if let Some(p) = percent.as_f64().filter(|p| p.fract() == 0_f64) {
+                *percent = (p as u64).into();
+            }
+        }
+    }
+    totals
 }
 
 /// Coverage for a single file
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
 pub struct File {
     /// List of Branches in the file
@@ -365,11 +599,224 @@ pub struct File {
     #[serde(skip_serializing_if = "Option::is_none")]
     segments: Option<Vec<Segment>>,
     /// Object summarizing the coverage for this file
-    summary: Summary,
+    pub summary: Summary,
+}
+
+impl File {
+    /// Returns (line, execution count) of each line that contains code, computed from segments
+    /// in the same way as `llvm-cov`.
+    ///
+    /// This is empty if report is summary-only.
+    // Refs: LineCoverageStats and LineCoverageIterator in https://github.com/llvm/llvm-project/blob/llvmorg-18.1.2/llvm/lib/ProfileData/Coverage/CoverageMapping.cpp
+    #[must_use]
+    pub fn line_execution_counts(&self) -> Vec<(u64, u64)> {
+        let segments = self.segments.as_deref().unwrap_or_default();
+        let mut lines = vec![];
+        // The last segment on the previous lines, whose region may continue to this line.
+        let mut wrapped: Option<&Segment> = None;
+        let mut line = 1;
+        let mut rest = segments;
+        while !rest.is_empty() {
+            let len = rest.iter().take_while(|s| s.line() <= line).count();
+            let (line_segments, next) = rest.split_at(len);
+            rest = next;
+
+            let is_start_of_region =
+                |s: &&Segment| !s.is_gap_region() && s.has_count() && s.is_region_entry();
+            let start_of_skipped_region =
+                line_segments.first().is_some_and(|s| !s.has_count() && s.is_region_entry());
+            let mapped = (!start_of_skipped_region
+                && (wrapped.is_some_and(Segment::has_count)
+                    || line_segments.iter().any(|s| is_start_of_region(&s))))
+                // A line that contains the start of a region with a counter is always mapped.
+                || line_segments.iter().any(|s| s.is_region_entry() && s.has_count());
+            if mapped {
+                let count = line_segments
+                    .iter()
+                    .filter(is_start_of_region)
+                    .map(Segment::count)
+                    .fold(wrapped.map_or(0, Segment::count), u64::max);
+                lines.push((line, count));
+            }
+
+            if let Some(last) = line_segments.last() {
+                wrapped = Some(last);
+            }
+            line += 1;
+        }
+        lines
+    }
+
+    /// Returns a file whose given lines are not covered.
+    fn uncovered(filename: String, lines: &[u64]) -> Self {
+        let mut summary = Summary::default();
+        summary.branches.notcovered = Some(0);
+        summary.regions.notcovered = Some(0);
+        summary.lines.count = lines.len() as u64;
+        let segments = lines
+            .iter()
+            .flat_map(|&line| {
+                [
+                    Segment(line, 1, 0, true, true, false),
+                    Segment(line, u32::MAX.into(), 0, false, false, false),
+                ]
+            })
+            .collect();
+        Self {
+            branches: Some(vec![]),
+            mcdc_records: None,
+            expansions: Some(vec![]),
+            filename,
+            segments: Some(segments),
+            summary,
+        }
+    }
+
+    /// Removes the given lines from segments and branches, and updates the line and branch
+    /// coverage of the summary.
+    ///
+    /// Branch coverage is updated from the branches of functions by [`Summary::exclude_functions`].
+    fn exclude_lines(&mut self, lines: &BTreeSet<u64>) {
+        // Count the removed lines directly instead of comparing line counts before and after
+        // excluding segments, which don't necessarily differ only in the excluded lines.
+        let (count, covered) = self
+            .line_execution_counts()
+            .into_iter()
+            .filter(|(line, _)| lines.contains(line))
+            .fold((0, 0), |(n, covered), (_, c)| (n + 1, covered + u64::from(c != 0)));
+        self.summary.lines.remove(count, covered);
+        if let Some(segments) = &mut self.segments {
+            *segments = exclude_segments(segments, lines);
+        }
+
+        let is_excluded = |b: &serde_json::Value| b[0].as_u64().is_some_and(|l| lines.contains(&l));
+        if let Some(branches) = &mut self.branches {
+            branches.retain(|b| !is_excluded(b));
+        }
+        for expansion in self.expansions.iter_mut().flatten() {
+            // Branches in macro expansions are attributed to the line where the macro is expanded.
+            if is_excluded(&expansion["source_region"]) {
+                if let Some(branches) = expansion["branches"].as_array_mut() {
+                    branches.clear();
+                }
+            }
+        }
+    }
+
+    /// Returns the execution counts of branches in the file, sorted by line.
+    ///
+    /// Same as `llvm-cov export -format=lcov`, branches in macro expansions are attributed to the
+    /// line where the macro is expanded.
+    ///
+    /// This is empty if report is summary-only.
+    #[must_use]
+    pub fn branch_execution_counts(&self) -> Vec<BranchCounts> {
+        fn branch(value: &serde_json::Value, line: Option<u64>) -> Option<BranchCounts> {
+            // LineStart, ColumnStart, LineEnd, ColumnEnd, ExecutionCount, FalseExecutionCount, FileID, ExpandedFileID, Kind
+            Some(BranchCounts {
+                line: match line {
+                    Some(line) => line,
+                    None => value[0].as_u64()?,
+                },
+                true_count: value[4].as_u64()?,
+                false_count: value[5].as_u64()?,
+            })
+        }
+
+        let mut branches: Vec<_> =
+            self.branches.iter().flatten().filter_map(|b| branch(b, None)).collect();
+        for expansion in self.expansions.iter().flatten() {
+            let Some(line) = expansion["source_region"][0].as_u64() else { continue };
+            if let Some(expansion_branches) = expansion["branches"].as_array() {
+                branches.extend(expansion_branches.iter().filter_map(|b| branch(b, Some(line))));
+            }
+        }
+        branches.sort_by_key(|b| b.line);
+        branches
+    }
+
+    /// Returns the execution count and branch counts of each line that contains code or
+    /// branches (line -> counts).
+    ///
+    /// This is empty if report is summary-only.
+    #[must_use]
+    pub fn line_branch_counts(&self) -> BTreeMap<u64, LineBranchCounts> {
+        let mut lines: BTreeMap<u64, LineBranchCounts> = self
+            .line_execution_counts()
+            .into_iter()
+            .map(|(line, count)| (line, LineBranchCounts { count, ..Default::default() }))
+            .collect();
+        for branch in self.branch_execution_counts() {
+            let line = lines.entry(branch.line).or_default();
+            // A line that has a branch that has been evaluated has been executed.
+            line.count = line.count.max(branch.true_count).max(branch.false_count);
+            line.branches += 2;
+            line.covered_branches +=
+                u64::from(branch.true_count != 0) + u64::from(branch.false_count != 0);
+        }
+        lines
+    }
+}
+
+/// Returns segments in which each range of the given lines is a skipped region.
+///
+/// The state of the segments at the end of each range is restored at the last column of the
+/// range, so lines after the range are not affected.
+fn exclude_segments(segments: &[Segment], lines: &BTreeSet<u64>) -> Vec<Segment> {
+    let mut out = Vec::with_capacity(segments.len());
+    let mut rest = segments.iter().peekable();
+    let mut lines = lines.iter().copied().peekable();
+    while let Some(start) = lines.next() {
+        let mut end = start;
+        while lines.next_if_eq(&(end + 1)).is_some() {
+            end += 1;
+        }
+        while let Some(s) = rest.next_if(|s| s.line() < start) {
+            out.push(s.clone());
+        }
+        let mut last = out.last().cloned();
+        while let Some(s) = rest.next_if(|s| s.line() <= end) {
+            last = Some(s.clone());
+        }
+        out.push(Segment(start, 1, 0, false, true, false));
+        if let Some(last) = last {
+            out.push(Segment(
+                end,
+                u32::MAX.into(),
+                last.count(),
+                last.has_count(),
+                false,
+                last.is_gap_region(),
+            ));
+        }
+    }
+    out.extend(rest.cloned());
+    out
+}
+
+/// Execution count and branch counts of a line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineBranchCounts {
+    /// Number of times the line has been executed.
+    pub count: u64,
+    /// Number of branches (both the true and false branches are counted).
+    pub branches: u64,
+    /// Number of branches that have been taken.
+    pub covered_branches: u64,
+}
+
+/// Execution counts of a branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BranchCounts {
+    pub line: u64,
+    /// Number of times the branch condition evaluated to true.
+    pub true_count: u64,
+    /// Number of times the branch condition evaluated to false.
+    pub false_count: u64,
 }
 
 /// Describes a segment of the file with a counter
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
 struct Segment(
     /* Line */ u64,
@@ -415,9 +862,9 @@ impl fmt::Debug for Segment {
 }
 
 /// Coverage info for a single function
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
-struct Function {
+pub struct Function {
     branches: Vec<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     mcdc_records: Option<Vec<serde_json::Value>>,
@@ -428,6 +875,67 @@ struct Function {
     regions: Vec<Region>,
 }
 
+impl Function {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of times the function has been executed.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns `true` if the main source file of the function is `filename`.
+    fn is_in(&self, filename: &str) -> bool {
+        self.filenames.first().is_some_and(|f| f == filename)
+    }
+
+    /// Returns the line where the first region of the function starts.
+    #[must_use]
+    pub fn start_line(&self) -> u64 {
+        self.regions.first().map_or(0, Region::line_start)
+    }
+
+    /// Returns the last line of the regions of the function in its main source file.
+    #[must_use]
+    pub fn end_line(&self) -> u64 {
+        self.regions.iter().filter(|r| r.file_id() == 0).map(Region::line_end).max().unwrap_or(0)
+    }
+
+    /// Returns the file name, location, and execution count of each code region of the function.
+    ///
+    /// The first region is the body of the function.
+    pub fn code_regions(&self) -> impl Iterator<Item = (&str, RegionLocation, u64)> {
+        // Only code regions have meaningful execution counts.
+        self.regions.iter().filter(|r| r.kind() == 0).filter_map(|r| {
+            let filename = self.filenames.get(usize::try_from(r.file_id()).ok()?)?;
+            Some((filename.as_str(), RegionLocation::from(r), r.execution_count()))
+        })
+    }
+
+    /// Returns the file name, location, and execution counts of each branch of the function.
+    pub fn branch_regions(&self) -> impl Iterator<Item = (&str, RegionLocation, BranchCounts)> {
+        self.branches.iter().filter_map(|b| {
+            // LineStart, ColumnStart, LineEnd, ColumnEnd, ExecutionCount, FalseExecutionCount, FileID, ExpandedFileID, Kind
+            let filename = self.filenames.get(usize::try_from(b[6].as_u64()?).ok()?)?;
+            let location = RegionLocation {
+                start_line: b[0].as_u64()?,
+                end_line: b[2].as_u64()?,
+                start_column: b[1].as_u64()?,
+                end_column: b[3].as_u64()?,
+            };
+            let counts = BranchCounts {
+                line: location.start_line,
+                true_count: b[4].as_u64()?,
+                false_count: b[5].as_u64()?,
+            };
+            Some((filename.as_str(), location, counts))
+        })
+    }
+}
+
 #[derive(Clone, Copy, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
 struct Region(
@@ -484,12 +992,14 @@ impl fmt::Debug for Region {
 }
 
 /// The location of a region
-#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
-struct RegionLocation {
-    start_line: u64,
-    end_line: u64,
-    start_column: u64,
-    end_column: u64,
+///
+/// Lines and columns are 1-based, and the end column is exclusive.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct RegionLocation {
+    pub start_line: u64,
+    pub end_line: u64,
+    pub start_column: u64,
+    pub end_column: u64,
 }
 
 impl From<&Region> for RegionLocation {
@@ -510,36 +1020,158 @@ impl RegionLocation {
 }
 
 /// Object summarizing the coverage for this file
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
-struct Summary {
+pub struct Summary {
     /// Object summarizing branch coverage
-    branches: CoverageCounts,
+    pub branches: CoverageCounts,
     /// Object summarizing mcdc coverage
     #[serde(skip_serializing_if = "Option::is_none")]
-    mcdc: Option<CoverageCounts>,
+    pub mcdc: Option<CoverageCounts>,
     /// Object summarizing function coverage
-    functions: CoverageCounts,
-    instantiations: CoverageCounts,
+    pub functions: CoverageCounts,
+    pub instantiations: CoverageCounts,
     /// Object summarizing line coverage
-    lines: CoverageCounts,
+    pub lines: CoverageCounts,
     /// Object summarizing region coverage
-    regions: CoverageCounts,
+    pub regions: CoverageCounts,
+}
+
+impl Summary {
+    #[must_use]
+    pub fn counts(&self, kind: CoverageKind) -> &CoverageCounts {
+        match kind {
+            CoverageKind::Functions => &self.functions,
+            CoverageKind::Lines => &self.lines,
+            CoverageKind::Regions => &self.regions,
+            CoverageKind::Branches => &self.branches,
+        }
+    }
+
+    /// Removes regions and branches in the given lines from the functions whose main source file
+    /// is `filename`, and updates the function, region, and branch coverage of this summary.
+    ///
+    /// Same as `llvm-cov`, instantiations of the same function are counted once, and a region or
+    /// branch is covered if it is covered in any of the instantiations.
+    fn exclude_functions(
+        &mut self,
+        functions: &mut [Function],
+        filename: &str,
+        lines: &BTreeSet<u64>,
+    ) {
+        fn count_covered(covered: impl IntoIterator<Item = bool>) -> (u64, u64) {
+            covered.into_iter().fold((0, 0), |(n, covered), c| (n + 1, covered + u64::from(c)))
+        }
+
+        let in_lines = |start: u64, end: u64| (start..=end).all(|l| lines.contains(&l));
+        // location -> covered
+        let mut removed_functions = BTreeMap::new();
+        let mut removed_instantiations = (0, 0);
+        let mut removed_regions = HashMap::new();
+        // location -> (true covered, false covered)
+        let mut removed_branches = BTreeMap::new();
+        for func in functions.iter_mut().filter(|f| f.is_in(filename)) {
+            let start = func.regions.first().map(|r| (r.line_start(), r.column_start()));
+            func.regions.retain(|region| {
+                // Regions of macro expansions are in other files.
+                if region.file_id() != 0 || !in_lines(region.line_start(), region.line_end()) {
+                    return true;
+                }
+                // Only code regions are counted in region coverage.
+                if region.kind() == 0 {
+                    let covered = removed_regions.entry(RegionLocation::from(region)).or_default();
+                    *covered = *covered || region.execution_count() > 0;
+                }
+                false
+            });
+            func.branches.retain(|b| {
+                // LineStart, ColumnStart, LineEnd, ColumnEnd, ExecutionCount, FalseExecutionCount, FileID, ExpandedFileID, Kind
+                let (Some(line), Some(file_id)) = (b[0].as_u64(), b[6].as_u64()) else {
+                    return true;
+                };
+                if file_id != 0 || !lines.contains(&line) {
+                    return true;
+                }
+                let loc = (line, b[1].as_u64(), b[2].as_u64(), b[3].as_u64());
+                let covered: &mut (bool, bool) = removed_branches.entry(loc).or_default();
+                covered.0 |= b[4].as_u64().is_some_and(|c| c > 0);
+                covered.1 |= b[5].as_u64().is_some_and(|c| c > 0);
+                false
+            });
+            if !func.regions.iter().any(|r| r.file_id() == 0) {
+                let covered = removed_functions.entry(start).or_default();
+                *covered = *covered || func.count > 0;
+                removed_instantiations.0 += 1;
+                removed_instantiations.1 += u64::from(func.count > 0);
+            }
+        }
+        let (count, covered) = count_covered(removed_functions.into_values());
+        self.functions.remove(count, covered);
+        self.instantiations.remove(removed_instantiations.0, removed_instantiations.1);
+        let (count, covered) = count_covered(removed_regions.into_values());
+        self.regions.remove(count, covered);
+        let (count, covered) =
+            count_covered(removed_branches.into_values().flat_map(|(t, f)| [t, f]));
+        self.branches.remove(count, covered);
+    }
+
+    /// Adds the coverage counts of `other` to this summary. (e.g., to aggregate summaries of files)
+    pub fn add(&mut self, other: &Self) {
+        self.branches.add(&other.branches);
+        if let Some(other) = &other.mcdc {
+            self.mcdc.get_or_insert_with(CoverageCounts::default).add(other);
+        }
+        self.functions.add(&other.functions);
+        self.instantiations.add(&other.instantiations);
+        self.lines.add(&other.lines);
+        self.regions.add(&other.regions);
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[cfg_attr(test, serde(deny_unknown_fields))]
-struct CoverageCounts {
-    count: u64,
-    covered: u64,
+pub struct CoverageCounts {
+    pub count: u64,
+    pub covered: u64,
     // Currently only branches and regions has this field.
     #[serde(skip_serializing_if = "Option::is_none")]
-    notcovered: Option<u64>,
-    percent: f64,
+    pub notcovered: Option<u64>,
+    pub percent: f64,
+}
+
+impl CoverageCounts {
+    fn add(&mut self, other: &Self) {
+        self.count += other.count;
+        self.covered += other.covered;
+        if let Some(notcovered) = other.notcovered {
+            *self.notcovered.get_or_insert(0) += notcovered;
+        }
+        self.update_percent();
+    }
+
+    /// Removes `count` items, `covered` of which are covered.
+    fn remove(&mut self, count: u64, covered: u64) {
+        if count == 0 {
+            return;
+        }
+        self.count = self.count.saturating_sub(count);
+        self.covered = self.covered.saturating_sub(covered);
+        if let Some(notcovered) = &mut self.notcovered {
+            *notcovered = notcovered.saturating_sub(count - covered);
+        }
+        self.update_percent();
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn update_percent(&mut self) {
+        // Same as llvm-cov, the percentage is 0 if there is nothing to cover.
+        self.percent =
+            if self.count == 0 { 0_f64 } else { self.covered as f64 / self.count as f64 * 100_f64 };
+    }
 }
 
 /// Information that is not part of the llvm-cov JSON export, but instead injected afterwards by us.
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 #[cfg_attr(test, derive(PartialEq))]
 struct CargoLlvmCov {
     /// Version of this project, which allows projects that depend on it, to express and verify
@@ -584,6 +1216,8 @@ mod tests {
             CoverageKind::Functions => 100_f64,
             CoverageKind::Lines => 57.142_857_142_857_146,
             CoverageKind::Regions => 61.538_461_538_461_54,
+            // Branch coverage is not enabled.
+            CoverageKind::Branches => 0_f64,
         };
 
         // There are 5 different percentages, make sure we pick the correct one.
@@ -613,6 +1247,11 @@ mod tests {
         test_get_coverage_percent(CoverageKind::Regions);
     }
 
+    #[test]
+    fn test_get_branches_percent() {
+        test_get_coverage_percent(CoverageKind::Branches);
+    }
+
     #[test]
     fn test_all_files_above_coverage() {
         let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
@@ -653,6 +1292,114 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_retain_files() {
+        let file = format!(
+            "{}/tests/fixtures/coverage-reports/real1/all.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let s = fs::read_to_string(file).unwrap();
+        let mut json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        json.retain_files(|f| f != "src/lib.rs");
+        let files: Vec<_> = json.data[0].files.iter().map(|f| &*f.filename).collect();
+        assert_eq!(files, ["member1/member2/src/lib.rs", "member1/src/lib.rs"]);
+        let totals = &json.data[0].totals;
+        assert_eq!(
+            totals["lines"],
+            serde_json::json!({ "count": 14, "covered": 8, "percent": 57.142_857_142_857_14 })
+        );
+        assert_eq!(
+            totals["functions"],
+            serde_json::json!({ "count": 2, "covered": 2, "percent": 100 })
+        );
+        assert_eq!(
+            totals["branches"],
+            serde_json::json!({ "count": 0, "covered": 0, "notcovered": 0, "percent": 0 })
+        );
+    }
+
+    #[test]
+    fn test_exclude_lines() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let mut json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        // Lines 7-9 are the whole of the uncovered function `bar`, and line 4 is a part of the
+        // region of the covered function `foo` (lines 3-5).
+        let excluded = [("src/lib.rs".to_owned(), [4, 7, 8, 9].into_iter().collect())];
+        json.exclude_lines(&excluded.into_iter().collect());
+        let file = &json.data[0].files[0];
+        assert_eq!(
+            file.line_execution_counts().iter().map(|&(line, _)| line).collect::<Vec<_>>(),
+            [1, 3, 5, 14, 15, 16, 17]
+        );
+        assert_eq!(file.summary.lines.count, 7);
+        assert_eq!(file.summary.lines.covered, 7);
+        assert_eq!(file.summary.functions.count, 4);
+        assert_eq!(file.summary.instantiations.count, 4);
+        assert_eq!(file.summary.regions.count, 4);
+        assert_eq!(file.summary.regions.notcovered, Some(0));
+        assert_eq!(json.data[0].functions.as_ref().unwrap().len(), 4);
+        assert_eq!(json.get_coverage_percent(CoverageKind::Lines).unwrap(), 100_f64);
+        assert_eq!(json.count_uncovered_regions().unwrap(), 0);
+        assert!(json.get_uncovered_lines(None).is_empty());
+        assert!(!json.get_line_execution_counts(None)["src/lib.rs"].contains_key(&4));
+    }
+
+    #[test]
+    fn test_add_unreported_files() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let s = fs::read_to_string(file).unwrap();
+        let mut json = serde_json::from_str::<LlvmCovJsonExport>(&s).unwrap();
+
+        json.add_unreported_files([("src/new.rs".to_owned(), vec![2, 3])].into_iter().collect());
+        let files: Vec<_> = json.data[0].files.iter().map(|f| &*f.filename).collect();
+        assert_eq!(files, ["src/lib.rs", "src/new.rs"]);
+        assert_eq!(json.data[0].files[1].line_execution_counts(), [(2, 0), (3, 0)]);
+        assert_eq!(
+            json.data[0].totals["lines"],
+            serde_json::json!({ "count": 13, "covered": 8, "percent": 61.538_461_538_461_54 })
+        );
+        assert_eq!(json.get_uncovered_lines(None)["src/new.rs"], [2, 3]);
+        assert!(!json.get_uncovered_lines(Some("new")).contains_key("src/new.rs"));
+        let codecov = serde_json::to_value(CodeCovJsonExport::new(&json, None)).unwrap();
+        assert_eq!(
+            codecov["coverage"]["src/new.rs"],
+            serde_json::json!({ "2": "0/1", "3": "0/1" })
+        );
+    }
+
+    #[test]
+    fn test_codecov_branches() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let mut json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+        // Two branches on line 4: the first one has been taken only in one direction, and the
+        // second one has been taken in both directions.
+        json["data"][0]["files"][0]["branches"] =
+            serde_json::json!([[4, 8, 4, 13, 1, 0, 0, 0, 4], [4, 17, 4, 22, 2, 1, 0, 0, 4],]);
+        let json: LlvmCovJsonExport = serde_json::from_value(json).unwrap();
+        let codecov = serde_json::to_value(CodeCovJsonExport::new(&json, None)).unwrap();
+        let lines = &codecov["coverage"]["src/lib.rs"];
+        assert_eq!(lines["4"], "3/4");
+        assert_eq!(lines["3"], "1/1");
+        assert_eq!(lines["7"], "0/1");
+    }
+
+    #[test]
+    fn test_line_branch_counts() {
+        let file = format!(
+            "{}/tests/fixtures/show-missing-lines-branches.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let json: LlvmCovJsonExport =
+            serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+        let lines = json.data[0].files[0].line_branch_counts();
+        assert_eq!(lines[&4], LineBranchCounts { count: 1, branches: 2, covered_branches: 1 });
+        assert_eq!(lines[&7], LineBranchCounts { count: 0, branches: 0, covered_branches: 0 });
+    }
+
     #[test]
     fn test_get_uncovered_lines() {
         // Given a coverage report which includes function regions:
@@ -671,6 +1418,46 @@ mod tests {
         assert_eq!(uncovered_lines, expected);
     }
 
+    #[test]
+    fn test_get_uncovered_regions() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let mut json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+        for f in json["data"][0]["functions"].as_array_mut().unwrap() {
+            if f["name"] == "_RNvCsaBRreq0qC0g_1t3foo" {
+                // An uncovered region in a covered region.
+                f["regions"]
+                    .as_array_mut()
+                    .unwrap()
+                    .push(serde_json::json!([4, 8, 4, 13, 0, 0, 0, 0]));
+            } else if f["name"] == "_RNvCsaBRreq0qC0g_1t3bar" {
+                // An uncovered region nested in another uncovered region.
+                f["regions"]
+                    .as_array_mut()
+                    .unwrap()
+                    .push(serde_json::json!([7, 5, 8, 2, 0, 0, 0, 0]));
+            }
+        }
+        let json: LlvmCovJsonExport = serde_json::from_value(json).unwrap();
+
+        let location = |start_line, start_column, end_line, end_column| RegionLocation {
+            start_line,
+            end_line,
+            start_column,
+            end_column,
+        };
+        let expected: UncoveredRegions = vec![("src/lib.rs".to_owned(), vec![
+            location(4, 8, 4, 13),
+            location(7, 1, 8, 13),
+            location(8, 13, 8, 14),
+            location(9, 1, 9, 2),
+        ])]
+        .into_iter()
+        .collect();
+        assert_eq!(json.get_uncovered_regions(None), expected);
+        assert_eq!(json.get_uncovered_regions(Some("lib")), UncoveredRegions::new());
+    }
+
     #[test]
     /// This was a case when counting line coverage based on the segments in files lead to
     /// incorrect results but doing it based on regions inside functions (the way `llvm-cov
@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// LCOV tracefile in the same format as `llvm-cov export -format=lcov`.
+// Refs: https://github.com/llvm/llvm-project/blob/llvmorg-18.1.2/llvm/tools/llvm-cov/CoverageExporterLcov.cpp
+
+use std::fmt::Write as _;
+
+use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+pub(crate) fn to_string(
+    json: &LlvmCovJsonExport,
+    summary_only: bool,
+    skip_functions: bool,
+) -> String {
+    let mut out = String::new();
+    for data in &json.data {
+        for file in &data.files {
+            let summary = &file.summary;
+            let _ = writeln!(out, "SF:{}", file.filename);
+            if !summary_only && !skip_functions {
+                let functions: Vec<_> = data.functions_in(&file.filename).collect();
+                for f in &functions {
+                    let _ = writeln!(out, "FN:{},{}", f.start_line(), f.name());
+                }
+                for f in &functions {
+                    let _ = writeln!(out, "FNDA:{},{}", f.count(), f.name());
+                }
+            }
+            let _ = writeln!(out, "FNF:{}", summary.functions.count);
+            let _ = writeln!(out, "FNH:{}", summary.functions.covered);
+            if !summary_only {
+                for (line, count) in file.line_execution_counts() {
+                    let _ = writeln!(out, "DA:{line},{count}");
+                }
+                let branches = file.branch_execution_counts();
+                // Branches on the same line are numbered individually (block) and as true/false
+                // pairs (branch).
+                for branches in branches.chunk_by(|a, b| a.line == b.line) {
+                    for (pair, branch) in branches.iter().enumerate() {
+                        let executed = branch.true_count != 0 || branch.false_count != 0;
+                        for (i, count) in
+                            [branch.true_count, branch.false_count].into_iter().enumerate()
+                        {
+                            let _ = write!(out, "BRDA:{},{pair},{}", branch.line, pair * 2 + i);
+                            if executed {
+                                let _ = writeln!(out, ",{count}");
+                            } else {
+                                let _ = writeln!(out, ",-");
+                            }
+                        }
+                    }
+                }
+            }
+            let _ = writeln!(out, "BRF:{}", summary.branches.count);
+            let _ = writeln!(out, "BRH:{}", summary.branches.covered);
+            let _ = writeln!(out, "LF:{}", summary.lines.count);
+            let _ = writeln!(out, "LH:{}", summary.lines.covered);
+            out.push_str("end_of_record\n");
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+    use crate::fs;
+
+    #[test]
+    fn summary_only() {
+        let model =
+            format!("{}/tests/fixtures/coverage-reports/real1/all", env!("CARGO_MANIFEST_DIR"));
+        let json: LlvmCovJsonExport =
+            serde_json::from_str(&fs::read_to_string(format!("{model}.json")).unwrap()).unwrap();
+        let expected = fs::read_to_string(format!("{model}.lcov.info")).unwrap();
+        assert_eq!(super::to_string(&json, true, false).trim_end(), expected.trim_end());
+    }
+
+    #[test]
+    fn full() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let json: LlvmCovJsonExport =
+            serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+        assert_eq!(
+            super::to_string(&json, false, false),
+            "SF:src/lib.rs\n\
+             FN:7,_RNvCsaBRreq0qC0g_1t3bar\n\
+             FN:14,_RNCNvNtCsaBRreq0qC0g_1t5tests8it_works0B5_\n\
+             FN:15,_RNvNtCsaBRreq0qC0g_1t5testss_8it_works\n\
+             FN:3,_RNvCsaBRreq0qC0g_1t3foo\n\
+             FN:1,_RNvCsaBRreq0qC0g_1t4main\n\
+             FNDA:0,_RNvCsaBRreq0qC0g_1t3bar\n\
+             FNDA:1,_RNCNvNtCsaBRreq0qC0g_1t5tests8it_works0B5_\n\
+             FNDA:1,_RNvNtCsaBRreq0qC0g_1t5testss_8it_works\n\
+             FNDA:1,_RNvCsaBRreq0qC0g_1t3foo\n\
+             FNDA:1,_RNvCsaBRreq0qC0g_1t4main\n\
+             FNF:5\n\
+             FNH:4\n\
+             DA:1,1\n\
+             DA:3,1\n\
+             DA:4,1\n\
+             DA:5,1\n\
+             DA:7,0\n\
+             DA:8,0\n\
+             DA:9,0\n\
+             DA:14,1\n\
+             DA:15,1\n\
+             DA:16,1\n\
+             DA:17,1\n\
+             BRF:0\n\
+             BRH:0\n\
+             LF:11\n\
+             LH:8\n\
+             end_of_record\n"
+        );
+    }
+}
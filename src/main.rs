@@ -27,22 +27,45 @@ mod term;
 #[macro_use]
 mod process;
 
+mod badge;
+mod baseline;
 mod cargo;
 mod clean;
 mod cli;
+mod cobertura;
+mod config;
 mod context;
+mod coveralls;
+mod csv;
 mod demangler;
+mod diff;
 mod env;
 mod fs;
+mod gcov;
+mod istanbul;
+mod jacoco;
+mod lcov;
+mod markdown;
+mod markers;
 mod metadata;
+mod openmetrics;
+mod per_test;
 mod regex_vec;
 mod report;
+mod sarif;
+mod select;
+mod sonarqube;
+mod sources;
+mod table;
 mod wrapper;
+mod xml;
 
 fn main() -> ExitCode {
     term::init_coloring();
     let res = if demangler::is_enabled() {
         demangler::try_main()
+    } else if per_test::is_enabled() {
+        per_test::try_main()
     } else if wrapper::is_enabled() {
         wrapper::try_main()
     } else {
@@ -64,6 +87,7 @@ fn try_main() -> Result<()> {
 
     match args.0.subcommand {
         Subcommand::Clean => clean::run(args)?,
+        Subcommand::Select => select::run(args)?,
         Subcommand::ShowEnv => {
             let cx = &Context::new(args)?;
             let writer = &mut ShowEnvWriter {
@@ -435,6 +459,9 @@ fn run_nextest(cx: &Context) -> Result<()> {
     let mut cargo = cx.cargo();
 
     set_env(cx, &mut cargo, IsNextest(true))?;
+    if cx.args.per_test {
+        per_test::set_env(cx, &mut cargo)?;
+    }
 
     cargo.arg("nextest").arg("run");
 
@@ -513,13 +540,9 @@ fn run_run(cx: &Context) -> Result<()> {
 }
 
 fn stdout_to_stderr(cx: &Context, cargo: &mut ProcessBuilder) {
-    if cx.args.report.no_report
-        || cx.args.report.output_dir.is_some()
-        || cx.args.report.output_path.is_some()
-    {
-        // Do not redirect if unnecessary.
-    } else {
-        // Redirect stdout to stderr as the report is output to stdout by default.
+    // Redirect stdout to stderr as the report is output to stdout by default.
+    // Do not redirect if unnecessary.
+    if report::print_to_stdout(&cx.args.report) {
         cargo.stdout_to_stderr();
     }
 }
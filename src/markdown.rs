@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Markdown summary for pull request comments and GitHub Actions job summaries.
+
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use camino::Utf8Path;
+use cargo_llvm_cov::json::{CoverageCounts, LlvmCovJsonExport, Summary};
+
+use crate::baseline::Baseline;
+
+/// The maximum number of files listed as the worst-covered files.
+const MAX_FILES: usize = 10;
+
+type Column = (&'static str, fn(&Summary) -> &CoverageCounts);
+
+const COLUMNS: &[Column] = &[
+    ("Functions", |s| &s.functions),
+    ("Lines", |s| &s.lines),
+    ("Regions", |s| &s.regions),
+    ("Branches", |s| &s.branches),
+];
+
+/// Returns the summary in Markdown format.
+///
+/// `package_of` returns the name of the package that contains the given file. If `baseline` is
+/// given, changes from it are shown next to the coverage.
+pub(crate) fn to_string<'a>(
+    json: &LlvmCovJsonExport,
+    workspace_root: &Utf8Path,
+    package_of: impl Fn(&str) -> Option<&'a str>,
+    baseline: Option<&Baseline>,
+) -> String {
+    let mut total = Summary::default();
+    let mut packages: BTreeMap<&str, Summary> = BTreeMap::new();
+    let mut files = vec![];
+    for file in json.data.iter().flat_map(|data| &data.files) {
+        total.add(&file.summary);
+        if let Some(package) = package_of(&file.filename) {
+            packages.entry(package).or_default().add(&file.summary);
+        }
+        // Same as baseline, file names are relative to the workspace root.
+        let name = Utf8Path::new(&file.filename)
+            .strip_prefix(workspace_root)
+            .map_or(&*file.filename, Utf8Path::as_str);
+        files.push((name, &file.summary));
+    }
+    let mut baseline_packages: BTreeMap<&str, Summary> = BTreeMap::new();
+    if let Some(baseline) = baseline {
+        for (name, summary) in baseline.files() {
+            if let Some(package) = package_of(name) {
+                baseline_packages.entry(package).or_default().add(summary);
+            }
+        }
+    }
+    // Branch coverage is shown only if it is enabled.
+    let columns = if total.branches.count == 0 { &COLUMNS[..3] } else { COLUMNS };
+
+    let mut out = String::new();
+    out.push_str("## Coverage\n\n");
+    let mut rows: Vec<_> = packages
+        .iter()
+        .map(|(&name, summary)| {
+            (format!("`{name}`"), summary, baseline.and(baseline_packages.get(name)))
+        })
+        .collect();
+    rows.push(("**Total**".to_owned(), &total, baseline.map(Baseline::totals)));
+    write_table(&mut out, "Package", columns, &rows);
+
+    // Files with uncovered lines, the worst-covered first.
+    files.retain(|(_, summary)| summary.lines.covered < summary.lines.count);
+    files.sort_by(|(a_name, a), (b_name, b)| {
+        a.lines.percent.total_cmp(&b.lines.percent).then_with(|| a_name.cmp(b_name))
+    });
+    files.truncate(MAX_FILES);
+    if !files.is_empty() {
+        out.push_str("\n<details>\n<summary>Files with the lowest line coverage</summary>\n\n");
+        let rows: Vec<_> = files
+            .iter()
+            .map(|&(name, summary)| {
+                (format!("`{name}`"), summary, baseline.and_then(|b| b.files().get(name)))
+            })
+            .collect();
+        write_table(&mut out, "File", columns, &rows);
+        out.push_str("\n</details>\n");
+    }
+    if baseline.is_some() {
+        out.push_str("\nChanges from the baseline are shown in percentage points.\n");
+    }
+    out
+}
+
+fn write_table(
+    out: &mut String,
+    header: &str,
+    columns: &[Column],
+    rows: &[(String, &Summary, Option<&Summary>)],
+) {
+    let _ = write!(out, "| {header} |");
+    for (title, _) in columns {
+        let _ = write!(out, " {title} |");
+    }
+    out.push_str("\n| :--- |");
+    for _ in columns {
+        out.push_str(" ---: |");
+    }
+    out.push('\n');
+    for (name, current, baseline) in rows {
+        let _ = write!(out, "| {name} |");
+        for (_, counts) in columns {
+            out.push(' ');
+            write_cell(out, counts(current), baseline.map(counts));
+            out.push_str(" |");
+        }
+        out.push('\n');
+    }
+}
+
+fn write_cell(out: &mut String, current: &CoverageCounts, baseline: Option<&CoverageCounts>) {
+    // Coverage is meaningless if there is nothing to cover.
+    if current.count == 0 {
+        out.push('-');
+        return;
+    }
+    let _ = write!(out, "{:.2}% ({}/{})", current.percent, current.covered, current.count);
+    if let Some(baseline) = baseline.filter(|b| b.count != 0) {
+        let delta = current.percent - baseline.percent;
+        if delta.abs() >= 0.005 {
+            let _ = write!(out, " {delta:+.2}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+    use crate::{baseline::Baseline, fs};
+
+    #[test]
+    fn markdown() {
+        let model = format!(
+            "{}/tests/fixtures/coverage-reports/real1/all.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let json: LlvmCovJsonExport =
+            serde_json::from_str(&fs::read_to_string(model).unwrap()).unwrap();
+        let package_of = |f: &str| {
+            Some(if f.starts_with("member1/member2/") {
+                "member2"
+            } else if f.starts_with("member1/") {
+                "member1"
+            } else {
+                "crate1"
+            })
+        };
+        assert_eq!(
+            super::to_string(&json, "/ws".into(), package_of, None),
+            "## Coverage\n\
+             \n\
+             | Package | Functions | Lines | Regions |\n\
+             | :--- | ---: | ---: | ---: |\n\
+             | `crate1` | 100.00% (2/2) | 84.62% (11/13) | 84.62% (11/13) |\n\
+             | `member1` | 100.00% (1/1) | 57.14% (4/7) | 57.14% (4/7) |\n\
+             | `member2` | 100.00% (1/1) | 57.14% (4/7) | 57.14% (4/7) |\n\
+             | **Total** | 100.00% (4/4) | 70.37% (19/27) | 70.37% (19/27) |\n\
+             \n\
+             <details>\n\
+             <summary>Files with the lowest line coverage</summary>\n\
+             \n\
+             | File | Functions | Lines | Regions |\n\
+             | :--- | ---: | ---: | ---: |\n\
+             | `member1/member2/src/lib.rs` | 100.00% (1/1) | 57.14% (4/7) | 57.14% (4/7) |\n\
+             | `member1/src/lib.rs` | 100.00% (1/1) | 57.14% (4/7) | 57.14% (4/7) |\n\
+             | `src/lib.rs` | 100.00% (2/2) | 84.62% (11/13) | 84.62% (11/13) |\n\
+             \n\
+             </details>\n"
+        );
+
+        let mut baseline = serde_json::to_value(Baseline::new(&json, "/ws".into())).unwrap();
+        // 10/13 -> 11/13 lines of src/lib.rs, 18/27 -> 19/27 lines in total.
+        let file = &mut baseline["files"]["src/lib.rs"]["lines"];
+        file["covered"] = 10.into();
+        file["percent"] = (10_f64 / 13_f64 * 100_f64).into();
+        let total = &mut baseline["totals"]["lines"];
+        total["covered"] = 18.into();
+        total["percent"] = (18_f64 / 27_f64 * 100_f64).into();
+        let baseline: Baseline = serde_json::from_value(baseline).unwrap();
+        let out = super::to_string(&json, "/ws".into(), package_of, Some(&baseline));
+        assert!(out.contains("| `crate1` | 100.00% (2/2) | 84.62% (11/13) +7.69 |"));
+        assert!(out.contains("| **Total** | 100.00% (4/4) | 70.37% (19/27) +3.70 |"));
+        assert!(out.contains("| `src/lib.rs` | 100.00% (2/2) | 84.62% (11/13) +7.69 |"));
+        assert!(out.ends_with("\nChanges from the baseline are shown in percentage points.\n"));
+    }
+}
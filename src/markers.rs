@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Source-comment markers that exclude lines from coverage.
+//
+// Supported markers (the LCOV_EXCL_* spellings are the same as lcov and grcov):
+// - `// cov:ignore-line` or `// LCOV_EXCL_LINE`: exclude the line
+// - `// cov:ignore-start` or `// LCOV_EXCL_START`: exclude lines until the end marker
+// - `// cov:ignore-end` or `// LCOV_EXCL_STOP`: end of the excluded lines (inclusive)
+//
+// Markers are only recognized at the start of `//` comments in Rust files in the `src`
+// directory of each measured package. String literals, char literals, and block comments are
+// skipped when looking for comments, so markers in them are ignored.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Result, bail};
+
+use crate::{context::Context, fs, sources};
+
+const LINE: &[&str] = &["cov:ignore-line", "LCOV_EXCL_LINE"];
+const START: &[&str] = &["cov:ignore-start", "LCOV_EXCL_START"];
+const END: &[&str] = &["cov:ignore-end", "LCOV_EXCL_STOP"];
+
+/// Returns lines excluded by markers in Rust files in the `src` directory of each measured
+/// package (file name -> lines).
+///
+/// File names are the same as the ones in the result of `llvm-cov export`.
+pub(crate) fn excluded_lines(cx: &Context) -> Result<BTreeMap<String, BTreeSet<u64>>> {
+    let mut files = BTreeMap::new();
+    for path in sources::rust_files(cx) {
+        let source = fs::read_to_string(&path)?;
+        // Fast path for files without markers.
+        if !LINE.iter().chain(START).chain(END).any(|marker| source.contains(marker)) {
+            continue;
+        }
+        let lines = parse(&source, path.as_str())?;
+        if !lines.is_empty() {
+            files.insert(sources::file_name(cx, &path).to_owned(), lines);
+        }
+    }
+    Ok(files)
+}
+
+fn parse(source: &str, filename: &str) -> Result<BTreeSet<u64>> {
+    let is = |comment: &str, markers: &[&str]| markers.iter().any(|m| comment.starts_with(m));
+    let mut lines = BTreeSet::new();
+    let mut start = None;
+    let mut state = State::Code;
+    for (i, line) in source.lines().enumerate() {
+        let line_no = i as u64 + 1;
+        let Some(comment) = line_comment(line, &mut state) else { continue };
+        let comment = comment.trim_start_matches('/').trim_start();
+        if is(comment, START) {
+            if let Some(start) = start {
+                bail!(
+                    "{filename}:{line_no}: nested exclusion start marker (previous one at {start})"
+                );
+            }
+            start = Some(line_no);
+        } else if is(comment, END) {
+            let Some(start) = start.take() else {
+                bail!("{filename}:{line_no}: exclusion end marker without start marker");
+            };
+            lines.extend(start..=line_no);
+        } else if is(comment, LINE) {
+            lines.insert(line_no);
+        }
+    }
+    if let Some(start) = start {
+        bail!("{filename}:{start}: exclusion start marker without end marker");
+    }
+    Ok(lines)
+}
+
+/// Lexical state carried over to the next line.
+#[derive(Clone, Copy)]
+enum State {
+    Code,
+    /// In a string literal.
+    Str,
+    /// In a raw string literal with the given number of `#`.
+    RawStr(usize),
+    /// In a block comment with the given nesting depth.
+    BlockComment(usize),
+}
+
+/// Returns the `//` comment in the given line, if any.
+///
+/// String literals, char literals, and block comments are skipped. Literals and comments that
+/// span multiple lines are tracked by `state`.
+fn line_comment<'a>(line: &'a str, state: &mut State) -> Option<&'a str> {
+    let is_ident = |c: u8| c.is_ascii_alphanumeric() || c == b'_';
+    let b = line.as_bytes();
+    let mut i = 0;
+    while i < b.len() {
+        match *state {
+            State::Str => match b[i] {
+                b'\\' => i += 1,
+                b'"' => *state = State::Code,
+                _ => {}
+            },
+            State::RawStr(hashes) => {
+                if b[i] == b'"' && b[i + 1..].iter().take_while(|&&c| c == b'#').count() >= hashes {
+                    *state = State::Code;
+                    i += hashes;
+                }
+            }
+            State::BlockComment(depth) => {
+                if b[i..].starts_with(b"*/") {
+                    *state = if depth == 1 { State::Code } else { State::BlockComment(depth - 1) };
+                    i += 1;
+                } else if b[i..].starts_with(b"/*") {
+                    *state = State::BlockComment(depth + 1);
+                    i += 1;
+                }
+            }
+            State::Code => match b[i] {
+                b'/' if b.get(i + 1) == Some(&b'/') => return Some(&line[i..]),
+                b'/' if b.get(i + 1) == Some(&b'*') => {
+                    *state = State::BlockComment(1);
+                    i += 1;
+                }
+                b'"' => *state = State::Str,
+                // Raw string literals (`r"..."`, `br#"..."#`, etc.).
+                b'r' if i == 0
+                    || !is_ident(b[i - 1])
+                    || (matches!(b[i - 1], b'b' | b'c') && (i == 1 || !is_ident(b[i - 2]))) =>
+                {
+                    let hashes = b[i + 1..].iter().take_while(|&&c| c == b'#').count();
+                    if b.get(i + 1 + hashes) == Some(&b'"') {
+                        *state = State::RawStr(hashes);
+                        i += hashes + 1;
+                    }
+                }
+                // Char literals. Otherwise, this is a lifetime or a label.
+                b'\'' => {
+                    let rest = &line[i + 1..];
+                    if rest.starts_with('\\') {
+                        i += rest
+                            .get(2..)
+                            .and_then(|r| r.find('\''))
+                            .map_or(rest.len(), |pos| pos + 3);
+                    } else if let Some(c) = rest.chars().next() {
+                        if rest[c.len_utf8()..].starts_with('\'') {
+                            i += c.len_utf8() + 1;
+                        }
+                    }
+                }
+                _ => {}
+            },
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn markers() {
+        // Use `#` instead of `//` so that markers in this file don't affect its own coverage.
+        let source = "\
+fn f(x: Option<u8>) -> u8 {
+    let Some(x) = x else {
+        unreachable!() # cov:ignore-line
+    };
+    # cov:ignore-start
+    if x == 0 {
+        return 0;
+    }
+    # cov:ignore-end
+    let s = \"cov:ignore-line\";
+    let s = (\"# cov:ignore-line\", '\"', r\"\\\", 'a);
+    let s = \"
+        # cov:ignore-line
+    \"; /* \" */ let c = '\\''; # cov:ignore-line
+    x # LCOV_EXCL_LINE
+}
+# LCOV_EXCL_START
+fn g() {} # not cov:ignore-line
+#LCOV_EXCL_STOP
+"
+        .replace('#', "//");
+        assert_eq!(parse(&source, "lib.rs").unwrap().into_iter().collect::<Vec<_>>(), [
+            3, 5, 6, 7, 8, 9, 14, 15, 17, 18, 19
+        ]);
+    }
+
+    #[test]
+    fn unmatched_markers() {
+        for source in [
+            "# cov:ignore-start\nfn f() {}\n",
+            "fn f() {}\n# cov:ignore-end\n",
+            "# cov:ignore-start\n# cov:ignore-start\n# cov:ignore-end\n",
+        ] {
+            let err = parse(&source.replace('#', "//"), "lib.rs").unwrap_err();
+            assert!(err.to_string().starts_with("lib.rs:"), "{err}");
+        }
+    }
+}
@@ -30,6 +30,8 @@ pub(crate) struct Metadata {
     pub(crate) target_directory: Utf8PathBuf,
     /// This is always `None` if running with a version of Cargo older than 1.91.
     build_directory: Option<Utf8PathBuf>,
+    /// The workspace-level metadata specified in `workspace.metadata` table of Cargo.toml.
+    pub(crate) workspace_metadata: Value,
 }
 
 impl Metadata {
@@ -78,6 +80,7 @@ impl Metadata {
             } else {
                 None
             },
+            workspace_metadata: map.remove("metadata").unwrap_or_default(),
         })
     }
 
@@ -103,6 +106,8 @@ pub(crate) struct Package {
     pub(crate) targets: Box<[Target]>,
     /// Absolute path to this package's manifest.
     pub(crate) manifest_path: Box<Utf8Path>,
+    /// The package-level metadata specified in `package.metadata` table of Cargo.toml.
+    pub(crate) metadata: Value,
 }
 
 impl Package {
@@ -119,6 +124,7 @@ impl Package {
                 .map(Target::from_value)
                 .collect::<Result<_, _>>()?,
             manifest_path: map.remove_string::<Utf8PathBuf>("manifest_path")?.into_boxed_path(),
+            metadata: map.remove("metadata").unwrap_or_default(),
         })
     }
 }
@@ -0,0 +1,130 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Coverage metrics in OpenMetrics text format.
+// Refs:
+// - https://github.com/prometheus/OpenMetrics/blob/main/specification/OpenMetrics.md
+// - https://github.com/prometheus/node_exporter#textfile-collector
+
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use camino::Utf8Path;
+use cargo_llvm_cov::json::{CoverageCounts, CoverageKind, LlvmCovJsonExport, Summary};
+
+const PREFIX: &str = "cargo_llvm_cov";
+
+const KINDS: [CoverageKind; 4] =
+    [CoverageKind::Functions, CoverageKind::Lines, CoverageKind::Regions, CoverageKind::Branches];
+
+/// Returns the metrics in OpenMetrics text format.
+///
+/// `package_of` returns the name of the package that contains the given file. Same as other
+/// reports, metrics of branch coverage are written only if it is enabled.
+pub(crate) fn to_string<'a>(
+    json: &LlvmCovJsonExport,
+    workspace_root: &Utf8Path,
+    package_of: impl Fn(&str) -> Option<&'a str>,
+) -> String {
+    let mut total = Summary::default();
+    let mut packages: BTreeMap<&str, Summary> = BTreeMap::new();
+    // (package, file name relative to the workspace root, coverage)
+    let mut files = vec![];
+    for file in json.data.iter().flat_map(|data| &data.files) {
+        let package = package_of(&file.filename);
+        total.add(&file.summary);
+        if let Some(package) = package {
+            packages.entry(package).or_default().add(&file.summary);
+        }
+        let name = Utf8Path::new(&file.filename)
+            .strip_prefix(workspace_root)
+            .map_or(&*file.filename, Utf8Path::as_str);
+        files.push((package, name, &file.summary));
+    }
+    // Series of the total coverage, coverage of each package, and coverage of each file.
+    let mut series = vec![(String::new(), &total)];
+    for (package, summary) in &packages {
+        series.push((labels(&[("package", package)]), summary));
+    }
+    for (package, name, summary) in files {
+        let labels = match package {
+            Some(package) => labels(&[("package", package), ("file", name)]),
+            None => labels(&[("file", name)]),
+        };
+        series.push((labels, summary));
+    }
+
+    let mut out = String::new();
+    for kind in KINDS {
+        if matches!(kind, CoverageKind::Branches) && total.branches.count == 0 {
+            continue;
+        }
+        let kind_name = kind.as_str();
+        let metrics: [(&str, &str, fn(&CoverageCounts) -> String); 3] = [
+            ("instrumented", "Number of instrumented", |c| c.count.to_string()),
+            ("covered", "Number of covered", |c| c.covered.to_string()),
+            ("coverage_ratio", "Ratio of covered", |c| ratio(c).to_string()),
+        ];
+        for (suffix, help, value) in metrics {
+            let name = format!("{PREFIX}_{kind_name}_{suffix}");
+            let _ = writeln!(out, "# HELP {name} {help} {kind_name}.");
+            let _ = writeln!(out, "# TYPE {name} gauge");
+            for (labels, summary) in &series {
+                let _ = writeln!(out, "{name}{labels} {}", value(summary.counts(kind)));
+            }
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+/// Returns the ratio of covered items, or 0 if there is nothing to cover.
+#[allow(clippy::cast_precision_loss)]
+fn ratio(counts: &CoverageCounts) -> f64 {
+    if counts.count == 0 { 0_f64 } else { counts.covered as f64 / counts.count as f64 }
+}
+
+/// Returns the label set in the form of `{name="value",...}`.
+fn labels(labels: &[(&str, &str)]) -> String {
+    let mut out = String::from("{");
+    for (i, (name, value)) in labels.iter().enumerate() {
+        if i != 0 {
+            out.push(',');
+        }
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+        let _ = write!(out, "{name}=\"{value}\"");
+    }
+    out.push('}');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+    use crate::fs;
+
+    #[test]
+    fn openmetrics() {
+        let model = format!(
+            "{}/tests/fixtures/coverage-reports/real1/all.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let json: LlvmCovJsonExport =
+            serde_json::from_str(&fs::read_to_string(model).unwrap()).unwrap();
+        let package_of = |f: &str| (!f.starts_with("member1/")).then_some("crate1");
+        let out = super::to_string(&json, "/ws".into(), package_of);
+        assert!(out.contains(
+            "# HELP cargo_llvm_cov_lines_covered Number of covered lines.\n\
+             # TYPE cargo_llvm_cov_lines_covered gauge\n\
+             cargo_llvm_cov_lines_covered 19\n\
+             cargo_llvm_cov_lines_covered{package=\"crate1\"} 11\n\
+             cargo_llvm_cov_lines_covered{file=\"member1/member2/src/lib.rs\"} 4\n\
+             cargo_llvm_cov_lines_covered{file=\"member1/src/lib.rs\"} 4\n\
+             cargo_llvm_cov_lines_covered{package=\"crate1\",file=\"src/lib.rs\"} 11\n"
+        ));
+        assert!(out.contains("\ncargo_llvm_cov_lines_coverage_ratio{package=\"crate1\"} 0.846"));
+        // Branch coverage is not enabled.
+        assert!(!out.contains("cargo_llvm_cov_branches"));
+        assert!(out.ends_with("\n# EOF\n"));
+        assert_eq!(super::labels(&[("file", "a\"b\\c")]), r#"{file="a\"b\\c"}"#);
+    }
+}
@@ -0,0 +1,363 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Per-test coverage for --per-test.
+//
+// Tests are run through cargo-llvm-cov as a target runner of nextest. The runner sets
+// LLVM_PROFILE_FILE to a directory for each test, based on the test binary ID and the
+// test name that nextest sets to the environment of each test.
+// Do NOT use the target runner directly since this is an unstable interface.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsString,
+    fmt::Write as _,
+    path, slice,
+    sync::Mutex,
+    thread,
+};
+
+use anyhow::{Context as _, Result, bail};
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_config2::Flags;
+use cargo_llvm_cov::json::LlvmCovJsonExport;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    EnvTarget, context::Context, env, fs, os_str_to_str, process::ProcessBuilder, report,
+    target_u_upper, term,
+};
+
+const RUNNER_ARG: &str = "__cargo-llvm-cov-per-test-runner";
+const ENV_DIR: &str = "__CARGO_LLVM_COV_PER_TEST_DIR";
+const ENV_PRE_EXISTING: &str = "__CARGO_LLVM_COV_PER_TEST_PRE_EXISTING_RUNNER";
+
+/// File in the directory of each test that contains the test ID.
+const TEST_FILE: &str = "test.json";
+
+#[derive(Serialize, Deserialize)]
+struct Test {
+    binary_id: String,
+    name: String,
+    /// Absolute path to the test binary.
+    #[serde(default)]
+    binary: Option<String>,
+}
+
+// -----------------------------------------------------------------------------
+// For caller
+
+pub(crate) fn set_env(cx: &Context, env: &mut dyn EnvTarget) -> Result<()> {
+    let exe = os_str_to_str(cx.current_exe.as_os_str())?;
+    // Cargo and nextest split the runner in the environment variable by spaces.
+    if exe.contains(' ') {
+        bail!("--per-test is not supported when path to cargo-llvm-cov contains spaces: {exe}");
+    }
+    let triple = cx.ws.target_for_config.triple();
+    env.set(
+        &format!("CARGO_TARGET_{}_RUNNER", target_u_upper(triple)),
+        &format!("{exe} {RUNNER_ARG}"),
+    )?;
+    env.set(ENV_DIR, cx.ws.per_test_dir.as_str())?;
+    if let Some(runner) = cx.ws.config.runner(&cx.ws.target_for_config)? {
+        let mut flags = Flags::default();
+        flags.push(os_str_to_str(runner.path.as_os_str())?);
+        for arg in &runner.args {
+            flags.push(os_str_to_str(arg)?);
+        }
+        env.set(ENV_PRE_EXISTING, &flags.encode()?)?;
+    } else {
+        env.unset(ENV_PRE_EXISTING)?;
+    }
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------
+// For callee
+
+pub(crate) fn is_enabled() -> bool {
+    env::args_os().nth(1).is_some_and(|arg| arg == RUNNER_ARG)
+}
+
+pub(crate) fn try_main() -> Result<()> {
+    debug_assert!(is_enabled());
+
+    // The test binary is the first argument regardless of the pre-existing runner.
+    let binary = env::args_os().nth(2).context("invalid arguments for test runner")?;
+    let mut args = env::args_os().skip(2);
+    let mut cmd = if let Some(pre_existing_runner) = env::var(ENV_PRE_EXISTING)? {
+        let mut runner = Flags::from_encoded(&pre_existing_runner).flags.into_iter();
+        let mut cmd = ProcessBuilder::new(runner.next().context("invalid pre-existing runner")?);
+        cmd.args(runner);
+        cmd
+    } else {
+        ProcessBuilder::new(args.next().context("invalid arguments for test runner")?)
+    };
+    cmd.args(args);
+
+    // nextest sets these when running each test, but not when listing tests.
+    if let (Some(binary_id), Some(name)) =
+        (env::var("NEXTEST_BINARY_ID")?, env::var("NEXTEST_TEST_NAME")?)
+    {
+        let dir =
+            Utf8PathBuf::from(env::var_required(ENV_DIR)?).join(test_dir_name(&binary_id, &name));
+        fs::create_dir_all(&dir)?;
+        let binary = os_str_to_str(path::absolute(&binary)?.as_os_str())?.to_owned();
+        let test = Test { binary_id, name, binary: Some(binary) };
+        fs::write(dir.join(TEST_FILE), serde_json::to_string(&test)?)?;
+        cmd.env("LLVM_PROFILE_FILE", dir.join("%p-%m.profraw").as_str());
+    }
+    cmd.run()?;
+    Ok(())
+}
+
+/// Returns the name of the directory for the given test, which doesn't contain characters that
+/// have special meaning in LLVM_PROFILE_FILE or file paths.
+///
+/// This is the 64-bit FNV-1a hash of the test ID. Unlike `DefaultHasher`, this is stable across
+/// Rust releases, so the directories written by a cargo-llvm-cov built with another Rust
+/// release can be read by `cargo llvm-cov report --per-test`.
+fn test_dir_name(binary_id: &str, name: &str) -> String {
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for &b in binary_id.as_bytes().iter().chain(&[0]).chain(name.as_bytes()) {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    format!("{hash:016x}")
+}
+
+// -----------------------------------------------------------------------------
+// Report
+
+/// Lines executed by each test.
+///
+/// This is also used as the index of select subcommand.
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct PerTest {
+    /// Tests in `<binary-id> <test-name>` format.
+    pub(crate) tests: BTreeSet<String>,
+    /// File name -> line -> tests that executed the line.
+    ///
+    /// File names are relative to the workspace root if they are in the workspace.
+    pub(crate) files: BTreeMap<String, BTreeMap<u64, BTreeSet<String>>>,
+}
+
+impl PerTest {
+    pub(crate) fn load(path: &Utf8Path) -> Result<Self> {
+        serde_json::from_str(&fs::read_to_string(path)?)
+            .with_context(|| format!("failed to parse per-test coverage {path}"))
+    }
+
+    fn add(&mut self, test: &str, json: &LlvmCovJsonExport, workspace_root: &Utf8Path) {
+        self.tests.insert(test.to_owned());
+        for file in json.data.iter().flat_map(|data| &data.files) {
+            let name = Utf8Path::new(&file.filename)
+                .strip_prefix(workspace_root)
+                .map_or(&*file.filename, Utf8Path::as_str);
+            for (line, count) in file.line_execution_counts() {
+                if count != 0 {
+                    self.files
+                        .entry(name.to_owned())
+                        .or_default()
+                        .entry(line)
+                        .or_default()
+                        .insert(test.to_owned());
+                }
+            }
+        }
+    }
+
+    /// Returns HTML that shows the source code of each file annotated with tests that executed
+    /// each line.
+    fn to_html(&self, workspace_root: &Utf8Path) -> String {
+        let mut out = String::new();
+        out.push_str(
+            "<!DOCTYPE html>\n\
+             <html>\n\
+             <head>\n\
+             <meta charset=\"utf-8\">\n\
+             <title>Per-test coverage</title>\n\
+             <style>\n\
+             body { font-family: sans-serif; }\n\
+             table { border-collapse: collapse; }\n\
+             td { vertical-align: top; padding: 0 8px; }\n\
+             pre { margin: 0; }\n\
+             .line { text-align: right; color: #888; }\n\
+             .tests { text-align: right; white-space: nowrap; }\n\
+             tr.covered { background-color: #dfd; }\n\
+             </style>\n\
+             </head>\n\
+             <body>\n\
+             <h1>Per-test coverage</h1>\n",
+        );
+        let _ = writeln!(out, "<p>{} tests, {} files</p>", self.tests.len(), self.files.len());
+        for (name, lines) in &self.files {
+            let path = workspace_root.join(name);
+            let source = match fs::read_to_string(&path) {
+                Ok(source) => source,
+                Err(e) => {
+                    // e.g., files removed after running tests.
+                    warn!("skipped {path} in per-test coverage: {e}");
+                    continue;
+                }
+            };
+            let _ = writeln!(out, "<h2>{}</h2>\n<table>", escape(name));
+            for (i, code) in source.lines().enumerate() {
+                let line = i as u64 + 1;
+                if let Some(tests) = lines.get(&line) {
+                    let _ = write!(
+                        out,
+                        "<tr class=\"covered\"><td class=\"line\">{line}</td><td class=\"tests\">\
+                         <details><summary>{}</summary><ul>",
+                        tests.len()
+                    );
+                    for test in tests {
+                        let _ = write!(out, "<li>{}</li>", escape(test));
+                    }
+                    out.push_str("</ul></details></td>");
+                } else {
+                    let _ =
+                        write!(out, "<tr><td class=\"line\">{line}</td><td class=\"tests\"></td>");
+                }
+                let _ = writeln!(out, "<td><pre>{}</pre></td></tr>", escape(code));
+            }
+            out.push_str("</table>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Writes `per-test.json` and `per-test.html` that show which tests executed each line.
+pub(crate) fn generate(
+    cx: &Context,
+    object_files: &[OsString],
+    ignore_filename_regex: Option<&str>,
+) -> Result<()> {
+    let workspace_root = &cx.ws.metadata.workspace_root;
+    // (test, llvm-profdata merge command, llvm-cov export command)
+    let mut tests = vec![];
+    if cx.ws.per_test_dir.exists() {
+        for entry in fs::read_dir(&cx.ws.per_test_dir)? {
+            let dir: Utf8PathBuf = entry?.path().try_into()?;
+            let test_file = dir.join(TEST_FILE);
+            if !test_file.exists() {
+                continue;
+            }
+            let test: Test = serde_json::from_str(&fs::read_to_string(&test_file)?)
+                .with_context(|| format!("failed to parse {test_file}"))?;
+            let profraw_files: Vec<_> = glob::glob(
+                Utf8Path::new(&glob::Pattern::escape(dir.as_str())).join("*.profraw").as_str(),
+            )?
+            .filter_map(Result::ok)
+            .collect();
+            if profraw_files.is_empty() {
+                continue;
+            }
+            let profdata_file = dir.join("test.profdata");
+            let mut merge = report::llvm_profdata_merge(cx, &profdata_file);
+            merge.args(profraw_files).stdout_to_stderr();
+            // Only the binary of the test is needed, and exporting all object files takes a
+            // long time on large projects. Test data written by older versions doesn't have it.
+            let binary = test.binary.as_ref().map(OsString::from);
+            let export = report::llvm_cov_export_cmd(
+                cx,
+                &profdata_file,
+                binary.as_ref().map_or(object_files, slice::from_ref),
+                ignore_filename_regex,
+                false,
+            );
+            tests.push((test, merge, export));
+        }
+    }
+
+    // Process tests in parallel.
+    let verbose = term::verbose();
+    let per_test = Mutex::new(PerTest::default());
+    let jobs = thread::available_parallelism().map_or(1, usize::from).min(tests.len());
+    let tests = Mutex::new(tests.into_iter());
+    thread::scope(|s| {
+        let handles: Vec<_> = (0..jobs)
+            .map(|_| {
+                s.spawn(|| -> Result<()> {
+                    loop {
+                        let Some((test, merge, export)) = tests.lock().unwrap().next() else {
+                            break;
+                        };
+                        let id = format!("{} {}", test.binary_id, test.name);
+                        if verbose {
+                            status!("Running", "{merge}");
+                        }
+                        merge.run()?;
+                        if verbose {
+                            status!("Running", "{export}");
+                        }
+                        let json = serde_json::from_str::<LlvmCovJsonExport>(&export.read()?)
+                            .with_context(|| format!("failed to get json of {id}"))?;
+                        per_test.lock().unwrap().add(&id, &json, workspace_root);
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        handles.into_iter().try_for_each(|handle| handle.join().unwrap())
+    })?;
+    let per_test = per_test.into_inner().unwrap();
+    if per_test.tests.is_empty() {
+        bail!(
+            "not found per-test coverage data in {}; this may occur if tests have not been run \
+             with `cargo llvm-cov nextest --per-test`, or the version of cargo-nextest is too old \
+             to set NEXTEST_BINARY_ID and NEXTEST_TEST_NAME",
+            cx.ws.per_test_dir
+        );
+    }
+
+    let output_dir = cx.args.report.output_dir.as_ref().unwrap_or(&cx.ws.default_output_dir);
+    fs::create_dir_all(output_dir)?;
+    let path = output_dir.join("per-test.json");
+    fs::write(&path, serde_json::to_string(&per_test)?)?;
+    eprintln!();
+    status!("Finished", "per-test coverage saved to {path}");
+    let path = output_dir.join("per-test.html");
+    fs::write(&path, per_test.to_html(workspace_root))?;
+    status!("Finished", "per-test coverage saved to {path}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+    use super::PerTest;
+    use crate::fs;
+
+    #[test]
+    fn per_test() {
+        let file = format!("{}/tests/fixtures/show-missing-lines.json", env!("CARGO_MANIFEST_DIR"));
+        let json: LlvmCovJsonExport =
+            serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+        let mut per_test = PerTest::default();
+        per_test.add("t tests::a", &json, "/ws".into());
+        per_test.add("t tests::b", &json, "/ws".into());
+        assert_eq!(per_test.tests.len(), 2);
+        let lines = &per_test.files["src/lib.rs"];
+        assert_eq!(lines.keys().copied().collect::<Vec<_>>(), [1, 3, 4, 5, 14, 15, 16, 17]);
+        assert!(lines.values().all(|tests| tests.len() == 2));
+        assert_eq!(super::escape("<a href=\"&\">"), "&lt;a href=&quot;&amp;&quot;&gt;");
+        // The directory name must not change across Rust releases.
+        assert_eq!(super::test_dir_name("t", "tests::a"), "89c5c31d6ae6d731");
+    }
+}
@@ -5,9 +5,10 @@
 // - https://llvm.org/docs/CommandGuide/llvm-cov.html
 
 use std::{
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     ffi::{OsStr, OsString},
-    io::{self, BufRead as _, BufWriter, Read as _, Write as _},
+    io::{self, BufWriter, IsTerminal as _, Read as _, Write as _},
+    iter,
     path::Path,
     time::SystemTime,
 };
@@ -15,21 +16,29 @@ use std::{
 use anyhow::{Context as _, Result, bail};
 use camino::{Utf8Path, Utf8PathBuf};
 use cargo_config2::Color;
-use cargo_llvm_cov::json::{CodeCovJsonExport, CoverageKind, LlvmCovJsonExport};
+use cargo_llvm_cov::json::{
+    CodeCovJsonExport, CoverageKind, LineCounts, LlvmCovJsonExport, Summary, UncoveredLines,
+    UncoveredRegions,
+};
 use regex::Regex;
 use serde_derive::Deserialize;
 use tar::Archive;
+use termcolor::{ColorChoice, NoColor, StandardStream};
 use walkdir::WalkDir;
 
 use crate::{
+    badge,
+    baseline::Baseline,
     cargo::Workspace,
-    cli::ReportOptions,
+    cli::{Annotations, ReportOptions},
+    cobertura,
     context::Context,
-    demangler, env, fs,
+    coveralls, csv, demangler, diff, env, fs, gcov, istanbul, jacoco, lcov, markdown, markers,
     metadata::Metadata,
-    os_str_to_str,
+    openmetrics, os_str_to_str, per_test,
+    process::ProcessBuilder,
     regex_vec::{RegexVec, RegexVecBuilder},
-    term,
+    sarif, sonarqube, sources, table, term,
 };
 
 pub(crate) fn generate(cx: &Context) -> Result<()> {
@@ -37,40 +46,121 @@ pub(crate) fn generate(cx: &Context) -> Result<()> {
         return Ok(());
     }
 
-    if let Some(output_dir) = &cx.args.report.output_dir {
-        fs::create_dir_all(output_dir)?;
-        if cx.args.report.html {
-            fs::create_dir_all(output_dir.join("html"))?;
-        }
-        if cx.args.report.text {
-            fs::create_dir_all(output_dir.join("text"))?;
+    for format in ReportFormat::from_args(&cx.args.report) {
+        if let Some(output_dir) = format.output_dir(&cx.args.report) {
+            fs::create_dir_all(output_dir)?;
         }
     }
 
     merge_profraw(cx).context("failed to merge profile data")?;
 
     let object_files = object_files(cx).context("failed to collect object files")?;
-    let ignore_filename_regex = ignore_filename_regex(cx, &object_files)?;
-    let format = ReportFormat::from_args(&cx.args.report);
-    format
-        .generate_report(cx, &object_files, ignore_filename_regex.as_deref())
-        .context("failed to generate report")?;
-
-    if cx.args.report.fail_under_functions.is_some()
-        || cx.args.report.fail_under_lines.is_some()
-        || cx.args.report.fail_under_file_lines.is_some()
-        || cx.args.report.fail_under_regions.is_some()
-        || cx.args.report.fail_uncovered_functions.is_some()
-        || cx.args.report.fail_uncovered_lines.is_some()
-        || cx.args.report.fail_uncovered_regions.is_some()
-        || cx.args.report.show_missing_lines
-    {
-        let format = ReportFormat::Json;
-        let json = format
-            .get_json(cx, &object_files, ignore_filename_regex.as_ref())
-            .context("failed to get json")?;
+    let options = &cx.args.report;
+    let formats = ReportFormat::from_args(options);
+    let analyze = options.fail_under_functions.is_some()
+        || options.fail_under_lines.is_some()
+        || options.fail_under_file_lines.is_some()
+        || options.fail_under_regions.is_some()
+        || options.fail_uncovered_functions.is_some()
+        || options.fail_uncovered_lines.is_some()
+        || options.fail_uncovered_regions.is_some()
+        || options.show_missing_lines
+        || options.annotations.is_some()
+        || options.teamcity
+        || options.package_summary
+        || options.diff_base.is_some()
+        || options.diff_file.is_some()
+        || options.baseline.is_some()
+        || options.save_baseline.is_some()
+        || options.badge.is_some()
+        || options.shields_json.is_some();
+    let excluded_lines =
+        markers::excluded_lines(cx).context("failed to parse exclusion markers")?;
+    // The summary and lcov report are generated by llvm-cov unless the result of `llvm-cov
+    // export` is edited.
+    let edited = !excluded_lines.is_empty() || options.include_all_sources;
+    // All reports other than the summary, lcov, text, and html, and all analyses are generated
+    // from the result of a single `llvm-cov export` invocation. Details of files and functions
+    // are exported only when something needs them, because they can be very large.
+    let summary_only = !(options.show_missing_lines
+        || options.annotations.is_some()
+        || options.fail_uncovered_lines.is_some()
+        || options.diff_base.is_some()
+        || options.diff_file.is_some())
+        && formats.iter().all(|format| match format {
+            ReportFormat::Json | ReportFormat::Cobertura => options.summary_only,
+            ReportFormat::LCov => options.summary_only || !edited,
+            ReportFormat::Codecov
+            | ReportFormat::SonarQube
+            | ReportFormat::Coveralls
+            | ReportFormat::JaCoCo
+            | ReportFormat::Sarif
+            | ReportFormat::Istanbul
+            | ReportFormat::Gcov => false,
+            // Functions are not included in the summary.
+            ReportFormat::Csv => !options.csv_functions,
+            ReportFormat::None
+            | ReportFormat::Markdown
+            | ReportFormat::OpenMetrics
+            | ReportFormat::Text
+            | ReportFormat::Html => true,
+        })
+        // Excluding lines by markers requires details.
+        && excluded_lines.is_empty();
+    let (ignore_filename_regex, json) = ignore_filename_regex(cx, &object_files, summary_only)?;
+    let mut json = match json {
+        Some(mut json) => {
+            // --dep-coverage exports coverage of all files to determine files to ignore.
+            if let Some(re) = &ignore_filename_regex {
+                let re = Regex::new(re)?;
+                json.retain_files(|f| !re.is_match(f));
+            }
+            Some(json)
+        }
+        None if analyze || formats.iter().any(|&format| format.is_export(edited)) => Some(
+            llvm_cov_export(
+                cx,
+                &cx.ws.profdata_file,
+                &object_files,
+                ignore_filename_regex.as_deref(),
+                summary_only,
+            )
+            .context("failed to get json")?,
+        ),
+        None => None,
+    };
+    if let Some(json) = &mut json {
+        // Handle --include-all-sources.
+        let unreported_files = if options.include_all_sources {
+            sources::unreported_files(cx, json, ignore_filename_regex.as_deref())
+                .context("failed to collect source files")?
+        } else {
+            BTreeMap::new()
+        };
+        json.add_unreported_files(unreported_files);
+        // Handle exclusion markers.
+        json.exclude_lines(&excluded_lines);
+    }
+    ReportFormat::generate_reports(
+        &formats,
+        cx,
+        &object_files,
+        ignore_filename_regex.as_deref(),
+        json.as_mut(),
+        edited,
+    )
+    .context("failed to generate report")?;
+
+    if cx.args.per_test {
+        // Handle --per-test.
+        per_test::generate(cx, &object_files, ignore_filename_regex.as_deref())
+            .context("failed to generate per-test coverage")?;
+    }
 
-        if let Some(fail_under_functions) = cx.args.report.fail_under_functions {
+    if let Some(json) = json.filter(|_| analyze) {
+        if let Some(fail_under_functions) =
+            cx.args.report.fail_under_functions.as_ref().and_then(|t| t.total)
+        {
             // Handle --fail-under-functions.
             let functions_percent = json
                 .get_coverage_percent(CoverageKind::Functions)
@@ -80,7 +170,9 @@ pub(crate) fn generate(cx: &Context) -> Result<()> {
             }
         }
 
-        if let Some(fail_under_lines) = cx.args.report.fail_under_lines {
+        if let Some(fail_under_lines) =
+            cx.args.report.fail_under_lines.as_ref().and_then(|t| t.total)
+        {
             // Handle --fail-under-lines.
             let lines_percent = json
                 .get_coverage_percent(CoverageKind::Lines)
@@ -97,7 +189,9 @@ pub(crate) fn generate(cx: &Context) -> Result<()> {
             }
         }
 
-        if let Some(fail_under_regions) = cx.args.report.fail_under_regions {
+        if let Some(fail_under_regions) =
+            cx.args.report.fail_under_regions.as_ref().and_then(|t| t.total)
+        {
             // Handle --fail-under-regions.
             let regions_percent = json
                 .get_coverage_percent(CoverageKind::Regions)
@@ -139,31 +233,296 @@ pub(crate) fn generate(cx: &Context) -> Result<()> {
             // Handle --show-missing-lines.
             let uncovered_files = json.get_uncovered_lines(ignore_filename_regex.as_deref());
             if !uncovered_files.is_empty() {
-                let mut stdout = BufWriter::new(io::stdout().lock()); // Buffered because it is written with newline many times.
-                show_missing_lines(&mut stdout, &uncovered_files)?;
+                show_missing_lines(&mut summary_writer(cx), &uncovered_files)?;
+            }
+        }
+
+        let package_thresholds = [
+            (
+                "--fail-under-functions",
+                CoverageKind::Functions,
+                &cx.args.report.fail_under_functions,
+            ),
+            ("--fail-under-lines", CoverageKind::Lines, &cx.args.report.fail_under_lines),
+            ("--fail-under-regions", CoverageKind::Regions, &cx.args.report.fail_under_regions),
+        ];
+        if cx.args.report.package_summary
+            || package_thresholds
+                .iter()
+                .any(|(_, _, t)| t.as_ref().is_some_and(|t| !t.packages.is_empty()))
+        {
+            let packages = package_summaries(cx, &json);
+            if cx.args.report.package_summary {
+                // Handle --package-summary.
+                let mut total = Summary::default();
+                for summary in packages.values() {
+                    total.add(summary);
+                }
+                let rows: Vec<_> =
+                    packages.iter().map(|(&name, summary)| (name, summary)).collect();
+                let mut stdout = StandardStream::stdout(stdout_color(cx));
+                table::write(&mut stdout, "Package", &rows, &total)?;
+            }
+
+            for (flag, kind, threshold) in package_thresholds {
+                // Handle per-package thresholds of --fail-under-{functions,lines,regions}.
+                let Some(threshold) = threshold else { continue };
+                for (name, min) in &threshold.packages {
+                    if !cx
+                        .workspace_members
+                        .included
+                        .iter()
+                        .any(|&id| *cx.ws.metadata[id].name == **name)
+                    {
+                        bail!(
+                            "package `{name}` specified in {flag} is not a workspace member included in the report"
+                        );
+                    }
+                    // Packages without coverage data are treated as 0% covered.
+                    let percent = packages.get(&**name).map_or(0_f64, |s| s.counts(kind).percent);
+                    if percent < *min {
+                        error!(
+                            "{} coverage of package `{name}` ({percent:.2}%) is less than {min}%",
+                            kind.as_str()
+                        );
+                    }
+                }
+            }
+        }
+
+        let diff = if let Some(base) = &cx.args.report.diff_base {
+            Some(diff::from_git(&cx.ws.metadata.workspace_root, base)?)
+        } else if let Some(path) = &cx.args.report.diff_file {
+            Some(diff::from_file(&cx.ws.metadata.workspace_root, path)?)
+        } else {
+            None
+        };
+        if let Some(diff) = &diff {
+            // Handle --diff-base, --diff-file, and --fail-under-diff-lines.
+            let line_counts = json.get_line_execution_counts(ignore_filename_regex.as_deref());
+            let diff_coverage = DiffCoverage::new(cx, &diff.new_lines, &line_counts);
+            // Changed lines of files that have no coverage data are not counted, so warn about
+            // them instead of silently reporting higher coverage (e.g., if paths in the diff
+            // don't match).
+            let re = ignore_filename_regex.as_deref().map(Regex::new).transpose()?;
+            let files_with_data: BTreeSet<Utf8PathBuf> =
+                line_counts.keys().map(|file| cx.ws.metadata.workspace_root.join(file)).collect();
+            let unknown_files: Vec<&str> = diff
+                .new_lines
+                .keys()
+                .filter(|path| {
+                    path.extension() == Some("rs")
+                        && !files_with_data.contains(*path)
+                        && !re.as_ref().is_some_and(|re| re.is_match(path.as_str()))
+                })
+                .map(|path| path.as_str())
+                .collect();
+            if !unknown_files.is_empty() {
+                warn!(
+                    "changed lines in the following files are not counted in diff coverage because \
+                     they have no coverage data: {}",
+                    unknown_files.join(", ")
+                );
+            }
+            show_diff_coverage(&mut summary_writer(cx), &diff_coverage)?;
+            if let Some(fail_under_diff_lines) = cx.args.report.fail_under_diff_lines {
+                if diff_coverage.percent() < fail_under_diff_lines {
+                    term::error::set(true);
+                }
+            }
+        }
+        if let Some(format) = cx.args.report.annotations {
+            // Handle --annotations.
+            let workspace_root = &cx.ws.metadata.workspace_root;
+            let mut uncovered_files = json.get_uncovered_lines(ignore_filename_regex.as_deref());
+            // Column ranges of regions are only used in the quickfix format.
+            let mut uncovered_regions = match format {
+                Annotations::GitHub => UncoveredRegions::new(),
+                Annotations::Quickfix => {
+                    json.get_uncovered_regions(ignore_filename_regex.as_deref())
+                }
+            };
+            if let Some(diff) = &diff {
+                // Annotate only files changed by the diff.
+                uncovered_files
+                    .retain(|file, _| diff.new_lines.contains_key(&workspace_root.join(file)));
+                uncovered_regions
+                    .retain(|file, _| diff.new_lines.contains_key(&workspace_root.join(file)));
+            }
+            let root = match format {
+                // GitHub resolves file paths relative to the root of the repository checked out.
+                Annotations::GitHub => match env::var("GITHUB_WORKSPACE")? {
+                    Some(root) => Utf8PathBuf::from(root),
+                    None => workspace_root.clone(),
+                },
+                Annotations::Quickfix => workspace_root.clone(),
+            };
+            let mut stdout = BufWriter::new(io::stdout().lock()); // Buffered because it is written with newline many times.
+            write_annotations(
+                &mut stdout,
+                format,
+                workspace_root,
+                &root,
+                &uncovered_files,
+                &uncovered_regions,
+            )?;
+        }
+        if cx.args.report.teamcity {
+            // Handle --teamcity.
+            let mut total = Summary::default();
+            for file in json.data.iter().flat_map(|data| &data.files) {
+                total.add(&file.summary);
+            }
+            let packages = package_summaries(cx, &json);
+            let mut stdout = BufWriter::new(io::stdout().lock()); // Buffered because it is written with newline many times.
+            write_teamcity(&mut stdout, &total, &packages)?;
+        }
+
+        if let Some(path) = &cx.args.report.baseline {
+            // Handle --baseline, --baseline-tolerance, and --update-baseline.
+            let current = Baseline::new(&json, &cx.ws.metadata.workspace_root);
+            if cx.args.report.update_baseline && !path.exists() {
+                current.save(path)?;
+                status!("Created", "baseline {path}");
+            } else {
+                let baseline = Baseline::load(path)?;
+                let tolerance = cx.args.report.baseline_tolerance.unwrap_or(0_f64);
+                let regressions = baseline.regressions(&current, tolerance);
+                for r in &regressions {
+                    let kind = r.kind.as_str();
+                    let target = match r.file {
+                        Some(file) => format!("{kind} coverage of `{file}`"),
+                        None => format!("total {kind} coverage"),
+                    };
+                    error!(
+                        "{target} decreased from {:.2}% to {:.2}% (baseline: {path})",
+                        r.baseline, r.current
+                    );
+                }
+                if regressions.is_empty()
+                    && cx.args.report.update_baseline
+                    && baseline.improved(&current)
+                {
+                    current.save(path)?;
+                    status!("Updated", "baseline {path}");
+                }
+            }
+        }
+        if let Some(path) = &cx.args.report.save_baseline {
+            // Handle --save-baseline.
+            Baseline::new(&json, &cx.ws.metadata.workspace_root).save(path)?;
+            status!("Finished", "baseline saved to {path}");
+        }
+        if cx.args.report.badge.is_some() || cx.args.report.shields_json.is_some() {
+            // Handle --badge, --shields-json, --badge-metric, and --badge-colors.
+            let kind = cx.args.report.badge_metric.unwrap_or(CoverageKind::Lines);
+            let percent = json
+                .get_coverage_percent(kind)
+                .with_context(|| format!("failed to get {} coverage", kind.as_str()))?;
+            let colors = cx.args.report.badge_colors.clone().unwrap_or_default();
+            let color = colors.color(percent);
+            if let Some(path) = &cx.args.report.badge {
+                fs::write(path, badge::to_svg(percent, color))?;
+                status!("Finished", "badge saved to {path}");
+            }
+            if let Some(path) = &cx.args.report.shields_json {
+                fs::write(path, badge::to_shields_json(percent, color))?;
+                status!("Finished", "shields.io endpoint saved to {path}");
             }
         }
     }
 
     if cx.args.report.open {
-        let path = &cx.args.report.output_dir.as_ref().unwrap().join("html/index.html");
+        let path = &ReportFormat::Html.output_dir(&cx.args.report).unwrap().join("index.html");
         status!("Opening", "{path}");
         open_report(cx, path)?;
     }
     Ok(())
 }
 
-fn show_missing_lines(
-    out: &mut dyn io::Write,
-    uncovered_files: &cargo_llvm_cov::json::UncoveredLines,
-) -> Result<()> {
+/// Maps source files to the workspace packages whose manifest directory contains them.
+struct PackageMap<'a> {
+    workspace_root: &'a Utf8Path,
+    /// List of (manifest directory, package name), nested packages first.
+    packages: Vec<(&'a Utf8Path, &'a str)>,
+}
+
+impl<'a> PackageMap<'a> {
+    fn new(cx: &'a Context) -> Self {
+        let mut packages: Vec<_> = cx
+            .workspace_members
+            .included
+            .iter()
+            .map(|&id| {
+                let pkg = &cx.ws.metadata[id];
+                (pkg.manifest_path.parent().unwrap(), &*pkg.name)
+            })
+            .collect();
+        packages.sort_unstable_by_key(|(dir, _)| std::cmp::Reverse(dir.components().count()));
+        Self { workspace_root: &cx.ws.metadata.workspace_root, packages }
+    }
+
+    /// Returns the name of the package that contains the given file.
+    fn package_of(&self, filename: &str) -> Option<&'a str> {
+        self.locate(filename).map(|(name, _)| name)
+    }
+
+    /// Returns the name and the manifest directory of the package that contains the given file.
+    fn locate(&self, filename: &str) -> Option<(&'a str, &'a Utf8Path)> {
+        // File names are relative to the workspace root if --remap-path-prefix is used.
+        let path = self.workspace_root.join(filename);
+        self.packages.iter().find(|(dir, _)| path.starts_with(dir)).map(|&(dir, name)| (name, dir))
+    }
+}
+
+/// Aggregates coverage summaries of files for each package.
+///
+/// Files that do not belong to any workspace package are ignored.
+fn package_summaries<'a>(cx: &'a Context, json: &LlvmCovJsonExport) -> BTreeMap<&'a str, Summary> {
+    let package_map = PackageMap::new(cx);
+    let mut packages: BTreeMap<_, Summary> = BTreeMap::new();
+    for file in json.data.iter().flat_map(|data| &data.files) {
+        if let Some(name) = package_map.package_of(&file.filename) {
+            packages.entry(name).or_default().add(&file.summary);
+        }
+    }
+    packages
+}
+
+fn stdout_color(cx: &Context) -> ColorChoice {
+    match cx.ws.config.term.color {
+        Some(Color::Auto) | None if io::stdout().is_terminal() => ColorChoice::Auto,
+        Some(Color::Always) => ColorChoice::Always,
+        Some(Color::Auto | Color::Never) | None => ColorChoice::Never,
+    }
+}
+
+/// Returns the writer of the output of --show-missing-lines and --diff-base/--diff-file.
+///
+/// This is stderr if quickfix annotations are printed, so that stdout only contains entries
+/// that editors can load.
+fn summary_writer(cx: &Context) -> BufWriter<Box<dyn io::Write>> {
+    let out: Box<dyn io::Write> = if cx.args.report.annotations == Some(Annotations::Quickfix) {
+        Box::new(io::stderr().lock())
+    } else {
+        Box::new(io::stdout().lock())
+    };
+    BufWriter::new(out) // Buffered because it is written with newline many times.
+}
+
+fn show_missing_lines(out: &mut dyn io::Write, uncovered_files: &UncoveredLines) -> Result<()> {
     writeln!(out, "Uncovered Lines:")?;
-    for (file, lines) in uncovered_files {
+    write_lines(out, uncovered_files)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes lines of each file, aggregating consecutive line numbers. (e.g., `file: 1-3, 5, 7-8`)
+fn write_lines(out: &mut dyn io::Write, files: &UncoveredLines) -> Result<()> {
+    for (file, lines) in files {
         write!(out, "{file}: ")?;
         let mut first = true;
-        for segment in lines.chunk_by(|a, b| a.checked_add(1) == Some(*b)) {
-            let start = segment[0];
-            let end = segment[segment.len() - 1];
+        for (start, end) in line_ranges(lines) {
             if first {
                 first = false;
             } else {
@@ -177,6 +536,202 @@ fn show_missing_lines(
         }
         writeln!(out)?;
     }
+    Ok(())
+}
+
+/// Returns the ranges of consecutive line numbers as (start, end) pairs.
+fn line_ranges(lines: &[u64]) -> impl Iterator<Item = (u64, u64)> + '_ {
+    lines
+        .chunk_by(|a, b| a.checked_add(1) == Some(*b))
+        .map(|segment| (segment[0], segment[segment.len() - 1]))
+}
+
+/// Writes ranges of uncovered lines of each file as annotations in the given format.
+///
+/// In the quickfix format, uncovered regions are written with their end positions instead of
+/// lines if the file has them.
+/// File paths are written relative to `root` if possible.
+fn write_annotations(
+    out: &mut dyn io::Write,
+    format: Annotations,
+    workspace_root: &Utf8Path,
+    root: &Utf8Path,
+    lines: &UncoveredLines,
+    regions: &UncoveredRegions,
+) -> Result<()> {
+    let files: BTreeSet<&String> = lines.keys().chain(regions.keys()).collect();
+    for file in files {
+        // File names are relative to the workspace root if --remap-path-prefix is used.
+        let path = workspace_root.join(file);
+        let path = path.strip_prefix(root).unwrap_or(&path);
+        match format {
+            Annotations::GitHub => {
+                let Some(lines) = lines.get(file) else { continue };
+                // https://docs.github.com/en/actions/reference/workflow-commands-for-github-actions#setting-a-warning-message
+                let file = path
+                    .as_str()
+                    .replace('%', "%25")
+                    .replace('\r', "%0D")
+                    .replace('\n', "%0A")
+                    .replace(':', "%3A")
+                    .replace(',', "%2C");
+                for (start, end) in line_ranges(lines) {
+                    writeln!(out, "::warning file={file},line={start},endLine={end}::Uncovered")?;
+                }
+            }
+            Annotations::Quickfix => {
+                // Same as the error format of compilers, which is recognized by Vim's
+                // 'errorformat', Emacs's compilation-mode, and VS Code's $gcc problem matcher.
+                let ranges: Vec<(u64, u64, u64, Option<u64>)> = match regions.get(file) {
+                    Some(regions) => regions
+                        .iter()
+                        .map(|r| (r.start_line, r.start_column, r.end_line, Some(r.end_column)))
+                        .collect(),
+                    // Falls back to ranges of uncovered lines, which have no end column.
+                    None => line_ranges(&lines[file])
+                        .map(|(start, end)| (start, 1, end, None))
+                        .collect(),
+                };
+                for (start, column, end, end_column) in ranges {
+                    let n = end - start + 1;
+                    let s = if n == 1 { "" } else { "s" };
+                    match end_column {
+                        Some(end_column) => writeln!(
+                            out,
+                            "{path}:{start}:{column}: uncovered region ({n} line{s}, until {end}:{end_column})"
+                        )?,
+                        None => writeln!(
+                            out,
+                            "{path}:{start}:{column}: uncovered region ({n} line{s})"
+                        )?,
+                    }
+                }
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes the total coverage and coverage of each package as TeamCity build statistics.
+///
+/// Regions are reported as blocks. Kinds of coverage with nothing to cover (e.g., branches
+/// without --branch) are omitted.
+fn write_teamcity(
+    out: &mut dyn io::Write,
+    total: &Summary,
+    packages: &BTreeMap<&str, Summary>,
+) -> Result<()> {
+    // https://www.jetbrains.com/help/teamcity/service-messages.html#Reporting+Build+Statistics
+    // https://www.jetbrains.com/help/teamcity/custom-chart.html#Default+Statistics+Values+Provided+by+TeamCity
+    const KINDS: [(CoverageKind, &str); 4] = [
+        (CoverageKind::Lines, "L"),
+        (CoverageKind::Functions, "M"),
+        (CoverageKind::Regions, "B"),
+        (CoverageKind::Branches, "R"),
+    ];
+    for (package, summary) in
+        iter::once((None, total)).chain(packages.iter().map(|(&name, s)| (Some(name), s)))
+    {
+        // Statistics of each package are reported with the package name as suffix.
+        let suffix = match package {
+            Some(name) => format!(".{}", escape_teamcity(name)),
+            None => String::new(),
+        };
+        for (kind, key) in KINDS {
+            let counts = summary.counts(kind);
+            if counts.count == 0 {
+                continue;
+            }
+            for (key, value) in [
+                (format!("CodeCoverageAbs{key}Covered{suffix}"), counts.covered.to_string()),
+                (format!("CodeCoverageAbs{key}Total{suffix}"), counts.count.to_string()),
+                (format!("CodeCoverage{key}{suffix}"), format!("{:.2}", counts.percent)),
+            ] {
+                writeln!(out, "##teamcity[buildStatisticValue key='{key}' value='{value}']")?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Escapes the given string for a value of TeamCity service messages.
+fn escape_teamcity(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\'' | '|' | '[' | ']' => {
+                out.push('|');
+                out.push(c);
+            }
+            '\n' => out.push_str("|n"),
+            '\r' => out.push_str("|r"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Line coverage of lines added or modified by the diff.
+struct DiffCoverage {
+    /// Number of instrumented lines added or modified by the diff.
+    count: u64,
+    /// Number of covered lines added or modified by the diff.
+    covered: u64,
+    uncovered_lines: UncoveredLines,
+}
+
+impl DiffCoverage {
+    fn new(cx: &Context, changed_lines: &diff::ChangedLines, line_counts: &LineCounts) -> Self {
+        let mut count = 0;
+        let mut covered = 0;
+        let mut uncovered_lines = UncoveredLines::new();
+        for (file, lines) in line_counts {
+            // File names are relative to the workspace root if --remap-path-prefix is used.
+            let path = cx.ws.metadata.workspace_root.join(file);
+            let Some(changed) = changed_lines.get(&path) else { continue };
+            let mut uncovered = vec![];
+            for (&line, &exec_count) in lines {
+                if !changed.contains(&line) {
+                    continue;
+                }
+                count += 1;
+                if exec_count == 0 {
+                    uncovered.push(line);
+                } else {
+                    covered += 1;
+                }
+            }
+            if !uncovered.is_empty() {
+                uncovered_lines.insert(file.clone(), uncovered);
+            }
+        }
+        Self { count, covered, uncovered_lines }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn percent(&self) -> f64 {
+        if self.count == 0 {
+            // No instrumented lines have been changed.
+            return 100_f64;
+        }
+        self.covered as f64 * 100_f64 / self.count as f64
+    }
+}
+
+fn show_diff_coverage(out: &mut dyn io::Write, diff_coverage: &DiffCoverage) -> Result<()> {
+    writeln!(
+        out,
+        "Diff Line Coverage: {:.2}% ({}/{} changed lines covered)",
+        diff_coverage.percent(),
+        diff_coverage.covered,
+        diff_coverage.count
+    )?;
+    if !diff_coverage.uncovered_lines.is_empty() {
+        writeln!(out, "Uncovered Changed Lines:")?;
+        write_lines(out, &diff_coverage.uncovered_lines)?;
+    }
     out.flush()?;
     Ok(())
 }
@@ -198,13 +753,15 @@ fn open_report(cx: &Context, path: &Utf8Path) -> Result<()> {
 fn merge_profraw(cx: &Context) -> Result<()> {
     // Convert raw profile data.
     let mut input_files = String::new();
-    for path in glob::glob(
-        Utf8Path::new(&glob::Pattern::escape(cx.ws.target_dir.as_str())).join("*.profraw").as_str(),
-    )?
-    .filter_map(Result::ok)
-    {
-        input_files.push_str(os_str_to_str(path.as_os_str())?);
-        input_files.push('\n');
+    for pattern in [
+        Utf8Path::new(&glob::Pattern::escape(cx.ws.target_dir.as_str())).join("*.profraw"),
+        // Written by tests run with --per-test.
+        Utf8Path::new(&glob::Pattern::escape(cx.ws.per_test_dir.as_str())).join("*/*.profraw"),
+    ] {
+        for path in glob::glob(pattern.as_str())?.filter_map(Result::ok) {
+            input_files.push_str(os_str_to_str(path.as_os_str())?);
+            input_files.push('\n');
+        }
     }
     if input_files.is_empty() {
         if cx.ws.profdata_file.exists() {
@@ -218,23 +775,28 @@ fn merge_profraw(cx: &Context) -> Result<()> {
     }
     let input_files_path = &cx.ws.target_dir.join(format!("{}-profraw-list", cx.ws.name));
     fs::write(input_files_path, input_files)?;
+    let mut cmd = llvm_profdata_merge(cx, &cx.ws.profdata_file);
+    cmd.arg("-f").arg(input_files_path);
+    if term::verbose() {
+        status!("Running", "{cmd}");
+    }
+    cmd.stdout_to_stderr().run()?;
+    Ok(())
+}
+
+/// Returns `llvm-profdata merge` command to write merged profile data into `output`.
+///
+/// The caller should add input files.
+pub(crate) fn llvm_profdata_merge(cx: &Context, output: &Utf8Path) -> ProcessBuilder {
     let mut cmd = cx.process(&cx.llvm_profdata);
-    cmd.args(["merge", "-sparse"])
-        .arg("-f")
-        .arg(input_files_path)
-        .arg("-o")
-        .arg(&cx.ws.profdata_file);
+    cmd.args(["merge", "-sparse"]).arg("-o").arg(output);
     if let Some(mode) = &cx.args.report.failure_mode {
         cmd.arg(format!("-failure-mode={mode}"));
     }
     if let Some(flags) = &cx.llvm_profdata_flags {
         cmd.args(flags.split(' ').filter(|s| !s.trim_start().is_empty()));
     }
-    if term::verbose() {
-        status!("Running", "{cmd}");
-    }
-    cmd.stdout_to_stderr().run()?;
-    Ok(())
+    cmd
 }
 
 fn object_files(cx: &Context) -> Result<Vec<OsString>> {
@@ -638,10 +1200,28 @@ enum ReportFormat {
     Json,
     /// `llvm-cov export -format=lcov`
     LCov,
-    /// `llvm-cov export -format=lcov` later converted to XML
+    /// `llvm-cov export -format=text` later converted to Cobertura XML
     Cobertura,
-    /// `llvm-cov show -format=lcov` later converted to Codecov JSON
+    /// `llvm-cov export -format=text` later converted to Codecov JSON
     Codecov,
+    /// `llvm-cov export -format=text` later converted to SonarQube generic test coverage XML
+    SonarQube,
+    /// `llvm-cov export -format=text` later converted to Coveralls API job JSON
+    Coveralls,
+    /// `llvm-cov export -format=text` later converted to JaCoCo XML
+    JaCoCo,
+    /// `llvm-cov export -format=text` later converted to Markdown summary
+    Markdown,
+    /// `llvm-cov export -format=text` later converted to SARIF log
+    Sarif,
+    /// `llvm-cov export -format=text` later converted to CSV
+    Csv,
+    /// `llvm-cov export -format=text` later converted to Istanbul JSON
+    Istanbul,
+    /// `llvm-cov export -format=text` later converted to OpenMetrics text format
+    OpenMetrics,
+    /// `llvm-cov export -format=text` later converted to gcov text format
+    Gcov,
     /// `llvm-cov show -format=text`
     Text,
     /// `llvm-cov show -format=html`
@@ -649,42 +1229,111 @@ enum ReportFormat {
 }
 
 impl ReportFormat {
-    fn from_args(options: &ReportOptions) -> Self {
-        if options.json {
-            Self::Json
-        } else if options.lcov {
-            Self::LCov
-        } else if options.cobertura {
-            Self::Cobertura
-        } else if options.codecov {
-            Self::Codecov
-        } else if options.text {
-            Self::Text
-        } else if options.html {
-            Self::Html
-        } else {
-            Self::None
+    /// Returns the list of report formats specified by the arguments.
+    fn from_args(options: &ReportOptions) -> Vec<Self> {
+        let formats: Vec<_> = [
+            (Self::Json, options.json),
+            (Self::LCov, options.lcov),
+            (Self::Cobertura, options.cobertura),
+            (Self::Codecov, options.codecov),
+            (Self::SonarQube, options.sonarqube),
+            (Self::Coveralls, options.coveralls),
+            (Self::JaCoCo, options.jacoco),
+            (Self::Markdown, options.markdown),
+            (Self::Sarif, options.sarif),
+            (Self::Csv, options.csv),
+            (Self::Istanbul, options.istanbul),
+            (Self::OpenMetrics, options.openmetrics),
+            (Self::Gcov, options.gcov),
+            (Self::Text, options.text),
+            (Self::Html, options.html),
+        ]
+        .into_iter()
+        .filter_map(|(format, enabled)| enabled.then_some(format))
+        .collect();
+        if formats.is_empty() { vec![Self::None] } else { formats }
+    }
+
+    /// Returns `true` if this report is generated from the result of `llvm-cov export -format=text`.
+    ///
+    /// The summary and lcov report are generated from it only if it is `edited` (e.g., by
+    /// exclusion markers), because llvm-cov doesn't know about the changes.
+    const fn is_export(self, edited: bool) -> bool {
+        match self {
+            Self::None | Self::LCov => edited,
+            Self::Text | Self::Html => false,
+            _ => true,
         }
     }
 
     const fn llvm_cov_args(self) -> &'static [&'static str] {
         match self {
             Self::None => &["report"],
-            Self::Json | Self::Codecov => &["export", "-format=text"],
-            Self::LCov | Self::Cobertura => &["export", "-format=lcov"],
+            Self::LCov => &["export", "-format=lcov"],
+            Self::Json
+            | Self::Cobertura
+            | Self::Codecov
+            | Self::SonarQube
+            | Self::Coveralls
+            | Self::JaCoCo
+            | Self::Markdown
+            | Self::Sarif
+            | Self::Csv
+            | Self::Istanbul
+            | Self::OpenMetrics
+            | Self::Gcov => &["export", "-format=text"],
             Self::Text => &["show", "-format=text"],
             Self::Html => &["show", "-format=html"],
         }
     }
 
+    /// Returns the path to the file to write the report into.
+    ///
+    /// If this returns `None`, the report will be printed to stdout, or written to the directory
+    /// returned by [`Self::output_dir`].
+    fn output_path(self, options: &ReportOptions) -> Option<&Utf8Path> {
+        let path = match self {
+            Self::Json => options.json_output_path.as_deref(),
+            Self::LCov => options.lcov_output_path.as_deref(),
+            Self::Cobertura => options.cobertura_output_path.as_deref(),
+            Self::Codecov => options.codecov_output_path.as_deref(),
+            Self::SonarQube => options.sonarqube_output_path.as_deref(),
+            Self::Coveralls => options.coveralls_output_path.as_deref(),
+            Self::JaCoCo => options.jacoco_output_path.as_deref(),
+            Self::Markdown => options.markdown_output_path.as_deref(),
+            Self::Sarif => options.sarif_output_path.as_deref(),
+            Self::Csv => options.csv_output_path.as_deref(),
+            Self::Istanbul => options.istanbul_output_path.as_deref(),
+            Self::OpenMetrics => options.openmetrics_output_path.as_deref(),
+            Self::Text => options.text_output_path.as_deref(),
+            Self::None => None,
+            Self::Html | Self::Gcov => return None,
+        };
+        path.or(options.output_path.as_deref())
+    }
+
+    /// Returns the path to the directory to write the report into.
+    fn output_dir(self, options: &ReportOptions) -> Option<Utf8PathBuf> {
+        match self {
+            // This is always `Some` after Context::new if --html or --open is passed.
+            Self::Html => options.html_output_dir.clone(),
+            // This is always `Some` after Context::new if --gcov is passed.
+            Self::Gcov => options.gcov_output_dir.clone(),
+            Self::Text if self.output_path(options).is_none() => {
+                options.output_dir.as_ref().map(|dir| dir.join("text"))
+            }
+            _ => None,
+        }
+    }
+
     fn use_color(self, cx: &Context) -> Option<&'static str> {
-        if matches!(self, Self::Json | Self::LCov | Self::Html) {
+        if !matches!(self, Self::None | Self::Text) {
             // `llvm-cov export` doesn't have `-use-color` flag.
             // https://llvm.org/docs/CommandGuide/llvm-cov.html#llvm-cov-export
             // Color output cannot be disabled when generating html.
             return None;
         }
-        if self == Self::Text && cx.args.report.output_dir.is_some() {
+        if self.output_dir(&cx.args.report).is_some() {
             return Some("-use-color=0");
         }
         match cx.ws.config.term.color {
@@ -694,194 +1343,286 @@ impl ReportFormat {
         }
     }
 
-    fn generate_report(
+    fn llvm_cov_cmd(
         self,
         cx: &Context,
+        instr_profile: &Utf8Path,
         object_files: &[OsString],
         ignore_filename_regex: Option<&str>,
-    ) -> Result<()> {
+    ) -> ProcessBuilder {
         let mut cmd = cx.process(&cx.llvm_cov);
 
         cmd.args(self.llvm_cov_args());
         cmd.args(self.use_color(cx));
-        cmd.arg(format!("-instr-profile={}", cx.ws.profdata_file));
+        cmd.arg(format!("-instr-profile={instr_profile}"));
         cmd.args(object_files.iter().flat_map(|f| [OsStr::new("-object"), f]));
         if let Some(ignore_filename_regex) = ignore_filename_regex {
             cmd.arg("-ignore-filename-regex");
             cmd.arg(ignore_filename_regex);
         }
 
-        match self {
-            Self::Text | Self::Html => {
-                cmd.args([
-                    &format!("-show-instantiations={}", cx.args.report.show_instantiations),
-                    "-show-line-counts-or-regions",
-                    "-show-expansions",
-                    "-show-branches=count",
-                ]);
-                if cmd!(&cx.llvm_cov, "show", "--help")
-                    .read()
-                    .unwrap_or_default()
-                    .contains("-show-mcdc")
-                {
-                    // -show-mcdc requires LLVM 18+
-                    cmd.arg("-show-mcdc");
-                }
-                let mut demangler = OsString::from("-Xdemangler=");
-                demangler.push(&cx.current_exe);
-                cmd.arg(demangler);
-                demangler::set_env(&mut cmd);
-                if let Some(output_dir) = &cx.args.report.output_dir {
-                    if self == Self::Html {
-                        cmd.arg(format!("-output-dir={}", output_dir.join("html")));
-                    } else {
-                        cmd.arg(format!("-output-dir={}", output_dir.join("text")));
-                    }
-                }
+        if let Self::Text | Self::Html = self {
+            cmd.args([
+                &format!("-show-instantiations={}", cx.args.report.show_instantiations),
+                "-show-line-counts-or-regions",
+                "-show-expansions",
+                "-show-branches=count",
+            ]);
+            if cmd!(&cx.llvm_cov, "show", "--help")
+                .read()
+                .unwrap_or_default()
+                .contains("-show-mcdc")
+            {
+                // -show-mcdc requires LLVM 18+
+                cmd.arg("-show-mcdc");
             }
-            Self::Json | Self::LCov | Self::Cobertura | Self::Codecov => {
-                if cx.args.report.summary_only {
-                    cmd.arg("-summary-only");
-                }
-                if cx.args.report.skip_functions {
-                    cmd.arg("-skip-functions");
-                }
+            let mut demangler = OsString::from("-Xdemangler=");
+            demangler.push(&cx.current_exe);
+            cmd.arg(demangler);
+            demangler::set_env(&mut cmd);
+            if let Some(output_dir) = self.output_dir(&cx.args.report) {
+                cmd.arg(format!("-output-dir={output_dir}"));
             }
-            Self::None => {}
         }
 
         if let Some(flags) = &cx.llvm_cov_flags {
             cmd.args(flags.split(' ').filter(|s| !s.trim_start().is_empty()));
         }
+        cmd
+    }
 
-        if cx.args.report.cobertura {
-            if term::verbose() {
-                status!("Running", "{cmd}");
+    /// Generates reports in the given formats.
+    ///
+    /// Reports other than text and html (and the summary and lcov report if `json` is not
+    /// `edited`) are generated from `json`, which is `Some` if any of them is requested.
+    fn generate_reports(
+        formats: &[Self],
+        cx: &Context,
+        object_files: &[OsString],
+        ignore_filename_regex: Option<&str>,
+        json: Option<&mut LlvmCovJsonExport>,
+        edited: bool,
+    ) -> Result<()> {
+        let options = &cx.args.report;
+
+        if let Some(json) = json {
+            if formats.contains(&Self::Codecov) {
+                let cov = CodeCovJsonExport::new(json, ignore_filename_regex);
+                let out = serde_json::to_string(&cov)?;
+                write_report(Self::Codecov.output_path(options), &out)?;
             }
-            let lcov = cmd.read()?;
-            // Convert to XML
-            let cdata = lcov2cobertura::parse_lines(
-                lcov.as_bytes().lines(),
-                &cx.ws.metadata.workspace_root,
-                &[],
-            )?;
-            let demangler = lcov2cobertura::RustDemangler::new();
-            let now = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .context("SystemTime before UNIX EPOCH!")?
-                .as_secs();
-            let out = lcov2cobertura::coverage_to_string(&cdata, now, demangler)?;
-
-            if let Some(output_path) = &cx.args.report.output_path {
-                fs::write(output_path, out)?;
-                eprintln!();
-                status!("Finished", "report saved to {output_path}");
-            } else {
-                // write XML to stdout
-                println!("{out}");
+            if formats.contains(&Self::SonarQube) {
+                let out = sonarqube::to_string(json, &cx.ws.metadata.workspace_root);
+                write_report(Self::SonarQube.output_path(options), &out)?;
             }
-            return Ok(());
-        }
-
-        if cx.args.report.codecov {
-            if term::verbose() {
-                status!("Running", "{cmd}");
+            if formats.contains(&Self::Coveralls) {
+                let out = coveralls::to_string(json, &cx.ws.metadata.workspace_root)?;
+                write_report(Self::Coveralls.output_path(options), &out)?;
             }
-            let cov = cmd.read()?;
-            let cov: LlvmCovJsonExport = serde_json::from_str(&cov)?;
-            let cov = CodeCovJsonExport::from_llvm_cov_json_export(cov, ignore_filename_regex);
-            let out = serde_json::to_string(&cov)?;
-
-            if let Some(output_path) = &cx.args.report.output_path {
-                fs::write(output_path, out)?;
+            if formats.contains(&Self::JaCoCo) {
+                let package_map = PackageMap::new(cx);
+                let out = jacoco::to_string(json, &cx.ws.metadata.workspace_root, |filename| {
+                    package_map.locate(filename)
+                });
+                write_report(Self::JaCoCo.output_path(options), &out)?;
+            }
+            if formats.contains(&Self::Markdown) {
+                let package_map = PackageMap::new(cx);
+                // --baseline may specify a file that --update-baseline will create.
+                let baseline = match &options.baseline {
+                    Some(path) if path.exists() => Some(Baseline::load(path)?),
+                    _ => None,
+                };
+                let out = markdown::to_string(
+                    json,
+                    &cx.ws.metadata.workspace_root,
+                    |filename| package_map.package_of(filename),
+                    baseline.as_ref(),
+                );
+                write_report(Self::Markdown.output_path(options), &out)?;
+                if let Some(path) = env::var("GITHUB_STEP_SUMMARY")? {
+                    // https://docs.github.com/en/actions/reference/workflow-commands-for-github-actions#adding-a-job-summary
+                    let mut file = fs::OpenOptions::new().create(true).append(true).open(&path)?;
+                    file.write_all(out.as_bytes())?;
+                }
+            }
+            if formats.contains(&Self::Sarif) {
+                let levels = options.sarif_level.unwrap_or_default();
+                let out = sarif::to_string(json, &cx.ws.metadata.workspace_root, levels)?;
+                write_report(Self::Sarif.output_path(options), &out)?;
+            }
+            if formats.contains(&Self::Csv) {
+                let package_map = PackageMap::new(cx);
+                let root = &cx.ws.metadata.workspace_root;
+                let package_of = |filename: &str| package_map.package_of(filename);
+                let out = if options.csv_functions {
+                    csv::functions_to_string(json, root, package_of)
+                } else {
+                    csv::files_to_string(json, root, package_of)
+                };
+                write_report(Self::Csv.output_path(options), &out)?;
+            }
+            if formats.contains(&Self::Istanbul) {
+                let out = istanbul::to_string(json, &cx.ws.metadata.workspace_root)?;
+                write_report(Self::Istanbul.output_path(options), &out)?;
+            }
+            if formats.contains(&Self::OpenMetrics) {
+                let package_map = PackageMap::new(cx);
+                let out =
+                    openmetrics::to_string(json, &cx.ws.metadata.workspace_root, |filename| {
+                        package_map.package_of(filename)
+                    });
+                write_report(Self::OpenMetrics.output_path(options), &out)?;
+            }
+            if formats.contains(&Self::Gcov) {
+                let output_dir = Self::Gcov.output_dir(options).unwrap();
+                gcov::write(json, &cx.ws.metadata.workspace_root, &output_dir)?;
                 eprintln!();
-                status!("Finished", "report saved to {output_path}");
-            } else {
-                // write JSON to stdout
-                println!("{out}");
+                status!("Finished", "report saved to {output_dir}");
+            }
+            if formats.contains(&Self::Json) {
+                json.inject(cx.ws.current_manifest.clone());
+                let out = if options.summary_only || options.skip_functions {
+                    // Details may have been exported for other reports or analyses.
+                    let mut json = json.clone();
+                    json.remove_details(options.summary_only, options.skip_functions);
+                    serde_json::to_string(&json)?
+                } else {
+                    serde_json::to_string(json)?
+                };
+                write_report(Self::Json.output_path(options), &out)?;
+            }
+            if formats.contains(&Self::Cobertura) {
+                let package_map = PackageMap::new(cx);
+                let cobertura_options = cobertura::Options {
+                    workspace_root: &cx.ws.metadata.workspace_root,
+                    remap_path_prefix: cx.args.remap_path_prefix,
+                    skip_functions: options.skip_functions,
+                    timestamp: SystemTime::now()
+                        .duration_since(SystemTime::UNIX_EPOCH)
+                        .context("SystemTime before UNIX EPOCH!")?
+                        .as_secs(),
+                };
+                let out = cobertura::to_string(json, &cobertura_options, |filename| {
+                    package_map.locate(filename)
+                });
+                write_report(Self::Cobertura.output_path(options), &out)?;
+            }
+            if formats.contains(&Self::LCov) && Self::LCov.is_export(edited) {
+                let out = lcov::to_string(json, options.summary_only, options.skip_functions);
+                write_report(Self::LCov.output_path(options), &out)?;
+            }
+            if formats.contains(&Self::None) && Self::None.is_export(edited) {
+                if let Some(output_path) = Self::None.output_path(options) {
+                    let mut out = NoColor::new(vec![]);
+                    table::write_files(&mut out, json)?;
+                    write_report(Some(output_path), &String::from_utf8(out.into_inner())?)?;
+                } else {
+                    table::write_files(&mut StandardStream::stdout(stdout_color(cx)), json)?;
+                }
             }
-            return Ok(());
         }
 
-        if let Some(output_path) = &cx.args.report.output_path {
+        for format in [Self::None, Self::LCov, Self::Text, Self::Html] {
+            if !formats.contains(&format) || format.is_export(edited) {
+                continue;
+            }
+            let mut cmd =
+                format.llvm_cov_cmd(cx, &cx.ws.profdata_file, object_files, ignore_filename_regex);
+            if format == Self::LCov {
+                if options.summary_only {
+                    cmd.arg("-summary-only");
+                }
+                if options.skip_functions {
+                    cmd.arg("-skip-functions");
+                }
+            }
             if term::verbose() {
                 status!("Running", "{cmd}");
             }
-
-            let out = cmd.read()?;
-            if self == Self::Json {
-                let mut cov = serde_json::from_str::<LlvmCovJsonExport>(&out)?;
-                cov.inject(cx.ws.current_manifest.clone());
-                fs::write(output_path, serde_json::to_string(&cov)?)?;
+            if let Some(output_path) = format.output_path(options) {
+                write_report(Some(output_path), &cmd.read()?)?;
             } else {
-                fs::write(output_path, out)?;
-            }
-
-            eprintln!();
-            status!("Finished", "report saved to {output_path}");
-            return Ok(());
-        }
-
-        if term::verbose() {
-            status!("Running", "{cmd}");
-        }
-
-        if self == Self::Json {
-            let out = cmd.read()?;
-            let mut cov = serde_json::from_str::<LlvmCovJsonExport>(&out)?;
-            cov.inject(cx.ws.current_manifest.clone());
-
-            let mut stdout = BufWriter::new(io::stdout().lock()); // Buffered because it is written many times.
-            serde_json::to_writer(&mut stdout, &cov)?;
-            stdout.flush()?;
-        } else {
-            cmd.run()?;
-        }
-
-        if matches!(self, Self::Html | Self::Text) {
-            if let Some(output_dir) = &cx.args.report.output_dir {
-                eprintln!();
-                if self == Self::Html {
-                    status!("Finished", "report saved to {}", output_dir.join("html"));
-                } else {
-                    status!("Finished", "report saved to {}", output_dir.join("text"));
+                cmd.run()?;
+                if let Some(output_dir) = format.output_dir(options) {
+                    eprintln!();
+                    status!("Finished", "report saved to {output_dir}");
                 }
             }
         }
+
         Ok(())
     }
+}
 
-    /// Generates JSON to perform further analysis on it.
-    fn get_json(
-        self,
-        cx: &Context,
-        object_files: &[OsString],
-        ignore_filename_regex: Option<&String>,
-    ) -> Result<LlvmCovJsonExport> {
-        if let Self::Json = self {
-        } else {
-            bail!("requested JSON for non-JSON type");
-        }
+/// Runs `llvm-cov export -format=text` with the given profile data and parses its output.
+pub(crate) fn llvm_cov_export(
+    cx: &Context,
+    instr_profile: &Utf8Path,
+    object_files: &[OsString],
+    ignore_filename_regex: Option<&str>,
+    summary_only: bool,
+) -> Result<LlvmCovJsonExport> {
+    let cmd =
+        llvm_cov_export_cmd(cx, instr_profile, object_files, ignore_filename_regex, summary_only);
+    if term::verbose() {
+        status!("Running", "{cmd}");
+    }
+    let json = serde_json::from_str::<LlvmCovJsonExport>(&cmd.read()?)
+        .context("failed to parse json from llvm-cov")?;
+    Ok(json)
+}
 
-        let mut cmd = cx.process(&cx.llvm_cov);
-        cmd.args(self.llvm_cov_args());
-        cmd.arg(format!("-instr-profile={}", cx.ws.profdata_file));
-        cmd.args(object_files.iter().flat_map(|f| [OsStr::new("-object"), f]));
-        if let Some(ignore_filename_regex) = ignore_filename_regex {
-            cmd.arg("-ignore-filename-regex");
-            cmd.arg(ignore_filename_regex);
-        }
-        if term::verbose() {
-            status!("Running", "{cmd}");
+/// Returns the `llvm-cov export -format=text` command used by [`llvm_cov_export`].
+pub(crate) fn llvm_cov_export_cmd(
+    cx: &Context,
+    instr_profile: &Utf8Path,
+    object_files: &[OsString],
+    ignore_filename_regex: Option<&str>,
+    summary_only: bool,
+) -> ProcessBuilder {
+    let mut cmd =
+        ReportFormat::Json.llvm_cov_cmd(cx, instr_profile, object_files, ignore_filename_regex);
+    if summary_only {
+        cmd.arg("-summary-only");
+    }
+    cmd
+}
+
+/// Writes a report to the given file, or stdout if no file is given.
+fn write_report(output_path: Option<&Utf8Path>, out: &str) -> Result<()> {
+    if let Some(output_path) = output_path {
+        fs::write(output_path, out)?;
+        eprintln!();
+        status!("Finished", "report saved to {output_path}");
+    } else {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(out.as_bytes())?;
+        if !out.ends_with('\n') {
+            stdout.write_all(b"\n")?;
         }
-        let cmd_out = cmd.read()?;
-        let json = serde_json::from_str::<LlvmCovJsonExport>(&cmd_out)
-            .context("failed to parse json from llvm-cov")?;
-        Ok(json)
+        stdout.flush()?;
     }
+    Ok(())
 }
 
-fn ignore_filename_regex(cx: &Context, object_files: &[OsString]) -> Result<Option<String>> {
+/// Returns `true` if any report will be printed to stdout.
+pub(crate) fn print_to_stdout(options: &ReportOptions) -> bool {
+    !options.no_report
+        && ReportFormat::from_args(options)
+            .into_iter()
+            .any(|f| f.output_path(options).is_none() && f.output_dir(options).is_none())
+}
+
+/// Returns the regex of files to ignore.
+///
+/// If --dep-coverage is used, this also returns the result of `llvm-cov export` without ignoring
+/// any files, which is used to determine the files to ignore.
+fn ignore_filename_regex(
+    cx: &Context,
+    object_files: &[OsString],
+    summary_only: bool,
+) -> Result<(Option<String>, Option<LlvmCovJsonExport>)> {
     // On Windows, we should escape the separator.
     const SEPARATOR: &str = if cfg!(windows) { "\\\\" } else { "/" };
 
@@ -905,6 +1646,7 @@ fn ignore_filename_regex(cx: &Context, object_files: &[OsString]) -> Result<Opti
     }
 
     let mut out = Out::default();
+    let mut dep_coverage_json = None;
 
     if let Some(ignore_filename) = &cx.args.report.ignore_filename_regex {
         out.push(ignore_filename);
@@ -968,8 +1710,8 @@ fn ignore_filename_regex(cx: &Context, object_files: &[OsString]) -> Result<Opti
                 }
             }
         } else {
-            let format = ReportFormat::Json;
-            let json = format.get_json(cx, object_files, None).context("failed to get json")?;
+            let json = llvm_cov_export(cx, &cx.ws.profdata_file, object_files, None, summary_only)
+                .context("failed to get json")?;
             let crates_io_re = Regex::new(&format!(
                 "{SEPARATOR}registry{SEPARATOR}src{SEPARATOR}index\\.crates\\.io-[0-9a-f]+{SEPARATOR}[0-9A-Za-z-_]+-[0-9]+\\.[0-9]+\\.[0-9]+(-[0-9A-Za-z\\.-]+)?(\\+[0-9A-Za-z\\.-]+)?{SEPARATOR}"
             ))?;
@@ -994,10 +1736,12 @@ fn ignore_filename_regex(cx: &Context, object_files: &[OsString]) -> Result<Opti
             for f in set {
                 out.push(f);
             }
+            dep_coverage_json = Some(json);
         }
     }
 
-    if out.0.is_empty() { Ok(None) } else { Ok(Some(out.0)) }
+    let re = if out.0.is_empty() { None } else { Some(out.0) };
+    Ok((re, dep_coverage_json))
 }
 
 fn resolve_excluded_paths(cx: &Context) -> Vec<Utf8PathBuf> {
@@ -1067,7 +1811,12 @@ fn resolve_excluded_paths(cx: &Context) -> Vec<Utf8PathBuf> {
 
 #[cfg(test)]
 mod tests {
-    use cargo_llvm_cov::json::UncoveredLines;
+    use cargo_llvm_cov::json::{
+        CoverageCounts, RegionLocation, Summary, UncoveredLines, UncoveredRegions,
+    };
+
+    use super::DiffCoverage;
+    use crate::cli::Annotations;
 
     #[test]
     fn show_missing_lines() {
@@ -1090,4 +1839,108 @@ mod tests {
         super::show_missing_lines(&mut o, &m).unwrap();
         assert_eq!(str::from_utf8(&o).unwrap(), "Uncovered Lines:\nf: 1-3, 5, 7-8\n");
     }
+
+    #[test]
+    fn write_annotations() {
+        let mut o = vec![];
+        let mut m = UncoveredLines::new();
+        m.insert("/other/lib.rs".to_owned(), vec![4]);
+        m.insert("/ws/crate/src/a,b.rs".to_owned(), vec![1, 2, 3, 5]);
+        // Relative to the workspace root if --remap-path-prefix is used.
+        m.insert("src/lib.rs".to_owned(), vec![7]);
+        let workspace_root = "/ws/crate".into();
+        let mut r = UncoveredRegions::new();
+        let location = |start_line, start_column, end_line, end_column| RegionLocation {
+            start_line,
+            end_line,
+            start_column,
+            end_column,
+        };
+        r.insert("src/lib.rs".to_owned(), vec![location(6, 9, 7, 6), location(9, 13, 9, 20)]);
+        super::write_annotations(&mut o, Annotations::GitHub, workspace_root, "/ws".into(), &m, &r)
+            .unwrap();
+        assert_eq!(
+            str::from_utf8(&o).unwrap(),
+            "::warning file=/other/lib.rs,line=4,endLine=4::Uncovered\n\
+             ::warning file=crate/src/a%2Cb.rs,line=1,endLine=3::Uncovered\n\
+             ::warning file=crate/src/a%2Cb.rs,line=5,endLine=5::Uncovered\n\
+             ::warning file=crate/src/lib.rs,line=7,endLine=7::Uncovered\n"
+        );
+
+        o.clear();
+        super::write_annotations(
+            &mut o,
+            Annotations::Quickfix,
+            workspace_root,
+            workspace_root,
+            &m,
+            &r,
+        )
+        .unwrap();
+        assert_eq!(
+            str::from_utf8(&o).unwrap(),
+            "/other/lib.rs:4:1: uncovered region (1 line)\n\
+             src/a,b.rs:1:1: uncovered region (3 lines)\n\
+             src/a,b.rs:5:1: uncovered region (1 line)\n\
+             src/lib.rs:6:9: uncovered region (2 lines, until 7:6)\n\
+             src/lib.rs:9:13: uncovered region (1 line, until 9:20)\n"
+        );
+    }
+
+    #[test]
+    fn write_teamcity() {
+        let counts =
+            |count, covered, percent| CoverageCounts { count, covered, notcovered: None, percent };
+        let total = Summary {
+            lines: counts(10, 8, 80_f64),
+            functions: counts(3, 1, 100_f64 / 3_f64),
+            ..Default::default()
+        };
+        let package = Summary {
+            lines: counts(10, 8, 80_f64),
+            branches: counts(4, 3, 75_f64),
+            ..Default::default()
+        };
+        let mut o = vec![];
+        super::write_teamcity(&mut o, &total, &[("a", package)].into_iter().collect()).unwrap();
+        assert_eq!(
+            str::from_utf8(&o).unwrap(),
+            "##teamcity[buildStatisticValue key='CodeCoverageAbsLCovered' value='8']\n\
+             ##teamcity[buildStatisticValue key='CodeCoverageAbsLTotal' value='10']\n\
+             ##teamcity[buildStatisticValue key='CodeCoverageL' value='80.00']\n\
+             ##teamcity[buildStatisticValue key='CodeCoverageAbsMCovered' value='1']\n\
+             ##teamcity[buildStatisticValue key='CodeCoverageAbsMTotal' value='3']\n\
+             ##teamcity[buildStatisticValue key='CodeCoverageM' value='33.33']\n\
+             ##teamcity[buildStatisticValue key='CodeCoverageAbsLCovered.a' value='8']\n\
+             ##teamcity[buildStatisticValue key='CodeCoverageAbsLTotal.a' value='10']\n\
+             ##teamcity[buildStatisticValue key='CodeCoverageL.a' value='80.00']\n\
+             ##teamcity[buildStatisticValue key='CodeCoverageAbsRCovered.a' value='3']\n\
+             ##teamcity[buildStatisticValue key='CodeCoverageAbsRTotal.a' value='4']\n\
+             ##teamcity[buildStatisticValue key='CodeCoverageR.a' value='75.00']\n"
+        );
+        assert_eq!(super::escape_teamcity("a'b|c[d]\n"), "a|'b||c|[d|]|n");
+    }
+
+    #[test]
+    fn show_diff_coverage() {
+        let mut o = vec![];
+
+        let mut uncovered_lines = UncoveredLines::new();
+        uncovered_lines.insert("f".to_owned(), vec![2, 3, 5]);
+        let diff_coverage = DiffCoverage { count: 4, covered: 1, uncovered_lines };
+        super::show_diff_coverage(&mut o, &diff_coverage).unwrap();
+        assert_eq!(
+            str::from_utf8(&o).unwrap(),
+            "Diff Line Coverage: 25.00% (1/4 changed lines covered)\nUncovered Changed Lines:\nf: 2-3, 5\n"
+        );
+
+        o.clear();
+        let diff_coverage =
+            DiffCoverage { count: 0, covered: 0, uncovered_lines: UncoveredLines::new() };
+        super::show_diff_coverage(&mut o, &diff_coverage).unwrap();
+        assert_eq!(
+            str::from_utf8(&o).unwrap(),
+            "Diff Line Coverage: 100.00% (0/0 changed lines covered)\n"
+        );
+    }
 }
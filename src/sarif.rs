@@ -0,0 +1,332 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// SARIF 2.1.0 log of uncovered code.
+// Refs: https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use anyhow::Result;
+use camino::Utf8Path;
+use cargo_llvm_cov::json::{self, LlvmCovJsonExport, RegionLocation};
+use serde_derive::Serialize;
+
+use crate::cli::SarifLevels;
+
+/// Base URI of file paths relative to the workspace root.
+const SRCROOT: &str = "%SRCROOT%";
+
+#[derive(Serialize)]
+struct Log {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: [Run; 1],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Run {
+    tool: Tool,
+    original_uri_base_ids: BTreeMap<&'static str, ArtifactLocation>,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    driver: Driver,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Driver {
+    name: &'static str,
+    version: &'static str,
+    information_uri: &'static str,
+    rules: Vec<Rule>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Rule {
+    id: &'static str,
+    short_description: Message,
+    default_configuration: Configuration,
+}
+
+#[derive(Serialize)]
+struct Configuration {
+    level: &'static str,
+}
+
+#[derive(Serialize)]
+struct Message {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: &'static str,
+    rule_index: usize,
+    level: &'static str,
+    message: Message,
+    locations: [Location; 1],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    physical_location: PhysicalLocation,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    region: Region,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactLocation {
+    uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri_base_id: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: u64,
+    start_column: u64,
+    end_line: u64,
+    end_column: u64,
+}
+
+/// (rule ID, description) of uncovered functions, regions, and branches.
+const RULES: [(&str, &str); 3] = [
+    ("uncovered-function", "Function is not covered"),
+    ("uncovered-region", "Region is not covered"),
+    ("uncovered-branch", "Branch is not fully covered"),
+];
+
+/// Returns the report in SARIF format.
+///
+/// Same as `llvm-cov`, instantiations of the same function are merged, and a function, region,
+/// or branch is covered if it is covered in any of the instantiations. Regions and branches in
+/// uncovered functions are not reported, because the function itself is reported.
+pub(crate) fn to_string(
+    json: &LlvmCovJsonExport,
+    workspace_root: &Utf8Path,
+    levels: SarifLevels,
+) -> Result<String> {
+    let rule_levels = [levels.function, levels.region, levels.branch];
+    let mut results = vec![];
+    for data in &json.data {
+        for file in &data.files {
+            // location of the function body -> (name, covered)
+            let mut functions: BTreeMap<RegionLocation, (&str, bool)> = BTreeMap::new();
+            // location -> (location of the function body, covered)
+            let mut regions: BTreeMap<RegionLocation, (RegionLocation, bool)> = BTreeMap::new();
+            // location -> (location of the function body, true covered, false covered)
+            let mut branches: BTreeMap<RegionLocation, (RegionLocation, bool, bool)> =
+                BTreeMap::new();
+            for f in data.functions_in(&file.filename) {
+                // Regions of macro expansions are in other files.
+                let mut code_regions = f.code_regions().filter(|&(name, ..)| name == file.filename);
+                let Some((_, body, _)) = code_regions.next() else { continue };
+                let function = functions.entry(body).or_insert((f.name(), false));
+                function.1 |= f.count() != 0;
+                for (_, location, count) in code_regions {
+                    regions.entry(location).or_insert((body, false)).1 |= count != 0;
+                }
+                for (_, location, counts) in
+                    f.branch_regions().filter(|&(name, ..)| name == file.filename)
+                {
+                    let branch = branches.entry(location).or_insert((body, false, false));
+                    branch.1 |= counts.true_count != 0;
+                    branch.2 |= counts.false_count != 0;
+                }
+            }
+
+            let is_covered = |body: &RegionLocation| functions.get(body).is_some_and(|f| f.1);
+            let mut file_results = vec![];
+            for (location, &(name, covered)) in &functions {
+                if !covered {
+                    let name = json::demangle(name);
+                    file_results.push((0, location, format!("Function `{name}` is not covered")));
+                }
+            }
+            for (location, (body, covered)) in &regions {
+                if !covered && is_covered(body) {
+                    file_results.push((1, location, "Region is not covered".to_owned()));
+                }
+            }
+            for (location, (body, true_covered, false_covered)) in &branches {
+                let message = match (true_covered, false_covered) {
+                    (true, true) => continue,
+                    (true, false) => "Branch condition was never false",
+                    (false, true) => "Branch condition was never true",
+                    (false, false) => "Branch condition was never evaluated",
+                };
+                if is_covered(body) {
+                    file_results.push((2, location, message.to_owned()));
+                }
+            }
+            file_results.sort_by_key(|&(rule, location, _)| {
+                (location.start_line, location.start_column, rule)
+            });
+
+            let (uri, uri_base_id) = uri(workspace_root, &file.filename);
+            for (rule_index, location, message) in file_results {
+                let level = rule_levels[rule_index];
+                results.push(SarifResult {
+                    rule_id: RULES[rule_index].0,
+                    rule_index,
+                    level: level.as_str(),
+                    message: Message { text: message },
+                    locations: [Location {
+                        physical_location: PhysicalLocation {
+                            artifact_location: ArtifactLocation { uri: uri.clone(), uri_base_id },
+                            region: Region {
+                                start_line: location.start_line,
+                                start_column: location.start_column,
+                                end_line: location.end_line,
+                                end_column: location.end_column,
+                            },
+                        },
+                    }],
+                });
+            }
+        }
+    }
+
+    let rules = RULES
+        .iter()
+        .zip(rule_levels)
+        .map(|(&(id, description), level)| Rule {
+            id,
+            short_description: Message { text: description.to_owned() },
+            default_configuration: Configuration { level: level.as_str() },
+        })
+        .collect();
+    let mut original_uri_base_ids = BTreeMap::new();
+    let mut root = file_uri(workspace_root.as_str());
+    if !root.ends_with('/') {
+        root.push('/');
+    }
+    original_uri_base_ids.insert(SRCROOT, ArtifactLocation { uri: root, uri_base_id: None });
+    let log = Log {
+        schema: "https://json.schemastore.org/sarif-2.1.0.json",
+        version: "2.1.0",
+        runs: [Run {
+            tool: Tool {
+                driver: Driver {
+                    name: env!("CARGO_PKG_NAME"),
+                    version: env!("CARGO_PKG_VERSION"),
+                    information_uri: env!("CARGO_PKG_REPOSITORY"),
+                    rules,
+                },
+            },
+            original_uri_base_ids,
+            results,
+        }],
+    };
+    Ok(serde_json::to_string(&log)?)
+}
+
+/// Returns the URI of the given file, relative to [`SRCROOT`] if it is in the workspace.
+fn uri(workspace_root: &Utf8Path, filename: &str) -> (String, Option<&'static str>) {
+    // File names are relative to the workspace root if --remap-path-prefix is used.
+    let path = workspace_root.join(filename);
+    match path.strip_prefix(workspace_root) {
+        Ok(path) => (percent_encode(path.as_str()), Some(SRCROOT)),
+        Err(_) => (file_uri(path.as_str()), None),
+    }
+}
+
+fn file_uri(path: &str) -> String {
+    let path = percent_encode(path);
+    // Windows paths (e.g., `C:/foo`) have no leading slash.
+    if path.starts_with('/') { format!("file://{path}") } else { format!("file:///{path}") }
+}
+
+fn percent_encode(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                out.push(b as char);
+            }
+            b'\\' => out.push('/'),
+            _ => {
+                let _ = write!(out, "%{b:02X}");
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+    use crate::{cli::SarifLevels, fs};
+
+    #[test]
+    fn sarif() {
+        let file = format!(
+            "{}/tests/fixtures/show-missing-lines-branches.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let json: LlvmCovJsonExport =
+            serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+        let levels = "function=error,branch=warning".parse::<SarifLevels>().unwrap();
+        let out: serde_json::Value =
+            serde_json::from_str(&super::to_string(&json, "/ws/a b".into(), levels).unwrap())
+                .unwrap();
+        let location = |start_line, start_column, end_line, end_column| {
+            serde_json::json!([{
+                "physicalLocation": {
+                    "artifactLocation": { "uri": "src/lib.rs", "uriBaseId": "%SRCROOT%" },
+                    "region": {
+                        "startLine": start_line,
+                        "startColumn": start_column,
+                        "endLine": end_line,
+                        "endColumn": end_column,
+                    },
+                },
+            }])
+        };
+        let run = &out["runs"][0];
+        assert_eq!(run["originalUriBaseIds"]["%SRCROOT%"]["uri"], "file:///ws/a%20b/");
+        assert_eq!(run["tool"]["driver"]["rules"][0]["defaultConfiguration"]["level"], "error");
+        assert_eq!(run["tool"]["driver"]["rules"][1]["defaultConfiguration"]["level"], "note");
+        assert_eq!(
+            run["results"],
+            serde_json::json!([
+                {
+                    "ruleId": "uncovered-region",
+                    "ruleIndex": 1,
+                    "level": "note",
+                    "message": { "text": "Region is not covered" },
+                    "locations": location(4, 8, 4, 13),
+                },
+                {
+                    "ruleId": "uncovered-branch",
+                    "ruleIndex": 2,
+                    "level": "warning",
+                    "message": { "text": "Branch condition was never false" },
+                    "locations": location(4, 8, 4, 13),
+                },
+                {
+                    "ruleId": "uncovered-function",
+                    "ruleIndex": 0,
+                    "level": "error",
+                    "message": { "text": "Function `t::bar` is not covered" },
+                    "locations": location(7, 1, 8, 13),
+                },
+            ])
+        );
+    }
+}
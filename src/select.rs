@@ -0,0 +1,213 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Test impact selection for select subcommand.
+//
+// The index is the per-test coverage written by --per-test. A test is selected if it executed
+// any line removed or modified by the diff, or any line next to the added lines, in the old files.
+// So the index should be built on the revision specified by --diff-base. Tests that are not in
+// the index (e.g., new tests) are always selected, and all tests are selected if the diff
+// changes files that have no coverage data in the index (e.g., new files and test files).
+//
+// Refs: https://nexte.st/docs/filtersets
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    io::{self, Write as _},
+};
+
+use anyhow::Result;
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{
+    cargo::Workspace,
+    cli::{self, Args, UnresolvedArgs},
+    diff::{self, Diff},
+    fs,
+    per_test::PerTest,
+    term,
+};
+
+pub(crate) fn run((mut args, mut unresolved_args): (Args, UnresolvedArgs)) -> Result<()> {
+    let mut ws = Workspace::new(unresolved_args.manifest_path.as_deref(), None, false)?;
+    cli::merge_config_and_args(&mut ws, &mut args, &mut unresolved_args)?;
+    term::set_coloring(&mut ws.config.term.color);
+    let workspace_root = &ws.metadata.workspace_root;
+
+    let index_path = match &args.select.index {
+        Some(path) => path.clone(),
+        None => ws.default_output_dir.join("per-test.json"),
+    };
+    let index = PerTest::load(&index_path)?;
+    let diff = if let Some(base) = &args.report.diff_base {
+        diff::from_git(workspace_root, base)?
+    } else {
+        diff::from_file(workspace_root, args.report.diff_file.as_deref().unwrap())?
+    };
+    let selection = Selection::new(&index, &diff, workspace_root);
+
+    if !selection.no_data.is_empty() {
+        warn!(
+            "{} tests have no recorded coverage in {index_path}, so they are always selected: {}",
+            selection.no_data.len(),
+            selection.no_data.join(", ")
+        );
+    }
+    if !selection.unknown_files.is_empty() {
+        let files: Vec<_> = selection.unknown_files.iter().map(|file| file.as_str()).collect();
+        warn!(
+            "the following files have been changed, but have no coverage data in {index_path}, \
+             so all tests are selected: {}",
+            files.join(", ")
+        );
+    }
+    // Changes not included in the diff cannot be taken into account.
+    let index_modified = fs::metadata(&index_path)?.modified()?;
+    for file in index.files.keys().map(|name| workspace_root.join(name)) {
+        if diff.old_lines.contains_key(&file) || diff.new_lines.contains_key(&file) {
+            continue;
+        }
+        if fs::metadata(&file).and_then(|m| m.modified()).is_ok_and(|m| m > index_modified) {
+            warn!(
+                "{file} has been modified after {index_path} was built, but is not included in \
+                 the diff; consider rebuilding the index"
+            );
+        }
+    }
+    info!(
+        "selected {} of {} tests in {index_path} (tests not in it are also selected)",
+        index.tests.len() - selection.skipped.len(),
+        index.tests.len()
+    );
+
+    if args.select.run {
+        let mut cmd = ws.cargo(args.verbose);
+        // The filterset matches no tests if no test is affected by the diff and no test is added.
+        cmd.args(["nextest", "run", "--no-tests=pass", "-E"]).arg(filterset(&selection.skipped));
+        if let Some(manifest_path) = &unresolved_args.manifest_path {
+            cmd.arg("--manifest-path").arg(manifest_path);
+        }
+        cmd.args(&args.build.rest);
+        if term::verbose() {
+            status!("Running", "{cmd}");
+        }
+        cmd.run()?;
+    } else {
+        let mut stdout = io::stdout().lock();
+        writeln!(stdout, "{}", filterset(&selection.skipped))?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+struct Selection<'a> {
+    /// Tests in the index that are not affected by the diff, in `<binary-id> <test-name>` format.
+    skipped: BTreeSet<&'a str>,
+    /// Tests that have no recorded coverage.
+    no_data: Vec<&'a str>,
+    /// Changed files that have no coverage data in the index.
+    unknown_files: Vec<&'a Utf8Path>,
+}
+
+impl<'a> Selection<'a> {
+    fn new(index: &'a PerTest, diff: &'a Diff, workspace_root: &Utf8Path) -> Self {
+        let files: BTreeMap<Utf8PathBuf, _> =
+            index.files.iter().map(|(name, lines)| (workspace_root.join(name), lines)).collect();
+        let mut tests = BTreeSet::new();
+        for (file, changed) in &diff.old_lines {
+            let Some(lines) = files.get(file) else { continue };
+            for line in changed {
+                if let Some(t) = lines.get(line) {
+                    tests.extend(t.iter().map(String::as_str));
+                }
+            }
+        }
+
+        let mut has_data = BTreeSet::new();
+        for t in index.files.values().flat_map(BTreeMap::values).flatten() {
+            has_data.insert(t.as_str());
+        }
+        let no_data: Vec<_> =
+            index.tests.iter().map(String::as_str).filter(|t| !has_data.contains(t)).collect();
+        tests.extend(&no_data);
+
+        let mut unknown_files: Vec<_> = diff
+            .old_lines
+            .keys()
+            .chain(diff.new_lines.keys())
+            .filter(|file| !files.contains_key(*file))
+            .map(Utf8PathBuf::as_path)
+            .collect();
+        unknown_files.sort_unstable();
+        unknown_files.dedup();
+
+        let skipped = if unknown_files.is_empty() {
+            index.tests.iter().map(String::as_str).filter(|t| !tests.contains(t)).collect()
+        } else {
+            BTreeSet::new()
+        };
+        Self { skipped, no_data, unknown_files }
+    }
+}
+
+/// Returns the nextest filterset that matches all tests other than the given tests.
+fn filterset(skipped: &BTreeSet<&str>) -> String {
+    fn escape(s: &str) -> String {
+        s.replace('\\', "\\\\").replace(')', "\\)").replace(',', "\\,")
+    }
+    if skipped.is_empty() {
+        return "all()".to_owned();
+    }
+    let mut out = String::new();
+    for test in skipped {
+        if !out.is_empty() {
+            out.push_str(" | ");
+        }
+        let (binary_id, name) = test.split_once(' ').unwrap_or(("", test));
+        let _ = write!(out, "(binary_id(={}) & test(={}))", escape(binary_id), escape(name));
+    }
+    format!("not ({out})")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use camino::Utf8Path;
+
+    use super::{Selection, filterset};
+    use crate::{diff::Diff, per_test::PerTest};
+
+    #[test]
+    fn select() {
+        let root = Utf8Path::new("/ws");
+        let mut index = PerTest::default();
+        for test in ["foo tests::a", "foo tests::b", "foo::bin/bar c", "foo tests::d"] {
+            index.tests.insert(test.to_owned());
+        }
+        let lines = index.files.entry("src/lib.rs".to_owned()).or_default();
+        lines.entry(1).or_default().extend(["foo tests::a".to_owned(), "foo tests::b".to_owned()]);
+        lines.entry(2).or_default().insert("foo tests::a".to_owned());
+        lines.entry(5).or_default().insert("foo::bin/bar c".to_owned());
+
+        let mut diff = Diff::default();
+        diff.old_lines.insert(root.join("src/lib.rs"), [2, 3].into_iter().collect());
+        diff.new_lines.insert(root.join("src/lib.rs"), [2].into_iter().collect());
+        let selection = Selection::new(&index, &diff, root);
+        assert_eq!(selection.skipped, ["foo tests::b", "foo::bin/bar c"].into_iter().collect());
+        assert_eq!(selection.no_data, ["foo tests::d"]);
+        assert!(selection.unknown_files.is_empty());
+
+        // All tests are selected if a file that has no coverage data is changed.
+        diff.new_lines.insert(root.join("tests/new.rs"), [1].into_iter().collect());
+        let selection = Selection::new(&index, &diff, root);
+        assert!(selection.skipped.is_empty());
+        assert_eq!(selection.unknown_files, [root.join("tests/new.rs")]);
+
+        assert_eq!(filterset(&BTreeSet::new()), "all()");
+        assert_eq!(
+            filterset(&["foo tests::a", "foo::bin/bar c"].into_iter().collect()),
+            "not ((binary_id(=foo) & test(=tests::a)) | (binary_id(=foo::bin/bar) & test(=c)))"
+        );
+    }
+}
@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// SonarQube generic test coverage report.
+// Refs: https://docs.sonarsource.com/sonarqube-server/latest/analyzing-source-code/test-coverage/generic-test-data/
+
+use std::fmt::Write as _;
+
+use camino::Utf8Path;
+use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+use crate::xml;
+
+/// Returns the report in SonarQube generic test coverage format.
+///
+/// File paths are relative to the workspace root if they are in the workspace, since SonarQube
+/// resolves relative paths from the project base directory.
+pub(crate) fn to_string(json: &LlvmCovJsonExport, workspace_root: &Utf8Path) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<coverage version=\"1\">\n");
+    for data in &json.data {
+        for file in &data.files {
+            let lines = file.line_branch_counts();
+            if lines.is_empty() {
+                continue;
+            }
+            let path = Utf8Path::new(&file.filename)
+                .strip_prefix(workspace_root)
+                .map_or(&*file.filename, Utf8Path::as_str);
+            let _ = writeln!(out, "  <file path=\"{}\">", xml::escape(path));
+            for (line, counts) in lines {
+                let _ = write!(
+                    out,
+                    "    <lineToCover lineNumber=\"{line}\" covered=\"{}\"",
+                    counts.count != 0
+                );
+                if counts.branches != 0 {
+                    let _ = write!(
+                        out,
+                        " branchesToCover=\"{}\" coveredBranches=\"{}\"",
+                        counts.branches, counts.covered_branches
+                    );
+                }
+                out.push_str("/>\n");
+            }
+            out.push_str("  </file>\n");
+        }
+    }
+    out.push_str("</coverage>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use cargo_llvm_cov::json::LlvmCovJsonExport;
+
+    use crate::fs;
+
+    #[test]
+    fn sonarqube() {
+        let file = format!(
+            "{}/tests/fixtures/show-missing-lines-branches.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+        json["data"][0]["files"][0]["filename"] = "/ws/src/lib.rs".into();
+        let json: LlvmCovJsonExport = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            super::to_string(&json, "/ws".into()),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <coverage version=\"1\">\n  \
+             <file path=\"src/lib.rs\">\n    \
+             <lineToCover lineNumber=\"1\" covered=\"true\"/>\n    \
+             <lineToCover lineNumber=\"3\" covered=\"true\"/>\n    \
+             <lineToCover lineNumber=\"4\" covered=\"true\" branchesToCover=\"2\" coveredBranches=\"1\"/>\n    \
+             <lineToCover lineNumber=\"5\" covered=\"true\"/>\n    \
+             <lineToCover lineNumber=\"7\" covered=\"false\"/>\n    \
+             <lineToCover lineNumber=\"8\" covered=\"false\"/>\n    \
+             <lineToCover lineNumber=\"9\" covered=\"false\"/>\n    \
+             <lineToCover lineNumber=\"14\" covered=\"true\"/>\n    \
+             <lineToCover lineNumber=\"15\" covered=\"true\"/>\n    \
+             <lineToCover lineNumber=\"16\" covered=\"true\"/>\n    \
+             <lineToCover lineNumber=\"17\" covered=\"true\"/>\n  \
+             </file>\n\
+             </coverage>\n"
+        );
+    }
+}
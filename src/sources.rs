@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Source files that are not in the coverage data for --include-all-sources.
+//
+// Files that are never compiled (e.g., feature-gated off) or never linked into the measured
+// binaries don't appear in the result of llvm-cov. Lines of such files can only be estimated
+// from the source code because there is no coverage mapping for them.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_llvm_cov::json::LlvmCovJsonExport;
+use regex::Regex;
+use walkdir::WalkDir;
+
+use crate::{context::Context, fs};
+
+/// Returns Rust files in the `src` directory of each measured package that are not in `json`
+/// (file name -> lines that contain code).
+pub(crate) fn unreported_files(
+    cx: &Context,
+    json: &LlvmCovJsonExport,
+    ignore_filename_regex: Option<&str>,
+) -> Result<BTreeMap<String, Vec<u64>>> {
+    let re = ignore_filename_regex.map(Regex::new).transpose()?;
+    let reported: BTreeSet<_> =
+        json.data.iter().flat_map(|data| &data.files).map(|file| &*file.filename).collect();
+    let mut files = BTreeMap::new();
+    for path in rust_files(cx) {
+        let name = file_name(cx, &path);
+        if reported.contains(name) || re.as_ref().is_some_and(|re| re.is_match(name)) {
+            continue;
+        }
+        let lines = code_lines(&fs::read_to_string(&path)?);
+        if !lines.is_empty() {
+            files.insert(name.to_owned(), lines);
+        }
+    }
+    Ok(files)
+}
+
+/// Returns the name of the given source file in the result of `llvm-cov export`.
+pub(crate) fn file_name<'a>(cx: &Context, path: &'a Utf8Path) -> &'a str {
+    // File names are relative to the workspace root if --remap-path-prefix is used.
+    if cx.args.remap_path_prefix {
+        path.strip_prefix(&cx.ws.metadata.workspace_root).unwrap_or(path).as_str()
+    } else {
+        path.as_str()
+    }
+}
+
+/// Returns Rust files in the `src` directory of each measured package.
+pub(crate) fn rust_files(cx: &Context) -> impl Iterator<Item = Utf8PathBuf> + '_ {
+    cx.workspace_members.included.iter().flat_map(|&id| {
+        let src_dir = cx.ws.metadata[id].manifest_path.parent().unwrap().join("src");
+        WalkDir::new(src_dir).into_iter().filter_map(Result::ok).filter_map(|entry| {
+            if !entry.file_type().is_file() || entry.path().extension().is_none_or(|e| e != "rs") {
+                return None;
+            }
+            Utf8PathBuf::from_path_buf(entry.into_path()).ok()
+        })
+    })
+}
+
+/// Returns lines that contain code.
+///
+/// Same as llvm-cov, only lines in function bodies are counted. Blank lines, comments,
+/// attributes, and lines that contain only brackets are not counted. Braces in string and char
+/// literals are not taken into account.
+fn code_lines(source: &str) -> Vec<u64> {
+    let mut lines = vec![];
+    let mut in_block_comment = false;
+    // The depth of braces.
+    let mut depth = 0_usize;
+    // The depth of braces outside the current function, and whether its body has been opened.
+    let mut func: Option<(usize, bool)> = None;
+    for (i, line) in source.lines().enumerate() {
+        let mut line = line.trim();
+        if in_block_comment {
+            let Some(end) = line.find("*/") else { continue };
+            in_block_comment = false;
+            line = line[end + 2..].trim_start();
+        }
+        if line.starts_with("/*") {
+            let Some(end) = line.find("*/") else {
+                in_block_comment = true;
+                continue;
+            };
+            line = line[end + 2..].trim_start();
+        }
+        let code = line.find("//").map_or(line, |pos| line[..pos].trim_end());
+        if code.is_empty() || code.starts_with('#') {
+            continue;
+        }
+        if func.is_none()
+            && code.split(|c: char| !c.is_alphanumeric() && c != '_').any(|w| w == "fn")
+            && !code.ends_with(';')
+        {
+            func = Some((depth, false));
+        }
+        if func.is_some()
+            && !code.chars().all(|c| matches!(c, '{' | '}' | '(' | ')' | '[' | ']' | ';' | ','))
+        {
+            lines.push(i as u64 + 1);
+        }
+        for c in code.chars() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        if let Some((outer, opened)) = &mut func {
+            *opened |= depth > *outer;
+            if *opened && depth <= *outer {
+                func = None;
+            }
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::code_lines;
+
+    #[test]
+    fn code_lines_of_source() {
+        let source = "\
+//! Crate docs
+#![no_std]
+
+use core::fmt;
+pub struct S {
+    x: u8,
+}
+impl S {
+    /* block
+       comment */
+    #[inline]
+    pub fn f(
+        &self,
+    ) -> u8 {
+        let f = |x: u8| { x };
+        f(self.x) + 1 // comment
+    }
+}
+trait T {
+    fn g();
+}
+";
+        assert_eq!(code_lines(source), [12, 13, 14, 15, 16]);
+    }
+}
@@ -0,0 +1,197 @@
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+// Coverage summary table in the same format as `llvm-cov report`.
+// Refs: https://github.com/llvm/llvm-project/blob/llvmorg-18.1.2/llvm/tools/llvm-cov/CoverageReport.cpp
+
+use std::{fmt, io};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_llvm_cov::json::{CoverageCounts, LlvmCovJsonExport, Summary};
+use termcolor::{Color, ColorSpec, WriteColor};
+
+/// Minimum width of the first column.
+const NAME_WIDTH: usize = 25;
+/// (header, width) of each column of each kind of coverage.
+const COLUMNS: [[(&str, usize); 3]; 4] = [
+    [("Regions", 12), ("Missed Regions", 18), ("Cover", 10)],
+    [("Functions", 12), ("Missed Functions", 18), ("Executed", 10)],
+    [("Lines", 12), ("Missed Lines", 18), ("Cover", 10)],
+    [("Branches", 12), ("Missed Branches", 18), ("Cover", 10)],
+];
+
+/// Writes a table that contains a row for each file, in the same way as `llvm-cov report`.
+pub(crate) fn write_files(out: &mut dyn WriteColor, json: &LlvmCovJsonExport) -> io::Result<()> {
+    let files: Vec<_> = json.data.iter().flat_map(|data| &data.files).collect();
+    let names = strip_redundant_prefix(files.iter().map(|f| &*f.filename).collect());
+    let mut rows = vec![];
+    let mut empty_rows = vec![];
+    let mut total = Summary::default();
+    for (name, file) in names.into_iter().zip(files) {
+        if file.summary.functions.count == 0 {
+            empty_rows.push((name, &file.summary));
+        } else {
+            rows.push((name, &file.summary));
+        }
+        total.add(&file.summary);
+    }
+    write_inner(out, "Filename", &rows, &empty_rows, &total)
+}
+
+/// Removes the path components shared by all file names, in the same way as `llvm-cov report`.
+fn strip_redundant_prefix(names: Vec<&str>) -> Vec<&str> {
+    // Keep the path as is if there is only one file.
+    if names.len() <= 1 {
+        return names;
+    }
+    let first: Vec<_> = Utf8Path::new(names[0]).components().collect();
+    let mut redundant = first.len();
+    for name in &names[1..] {
+        for (i, component) in Utf8Path::new(name).components().enumerate().take(redundant) {
+            if first[i] != component {
+                redundant = i;
+                break;
+            }
+        }
+    }
+    let prefix: Utf8PathBuf = first[..redundant].iter().collect();
+    names
+        .into_iter()
+        .map(|name| Utf8Path::new(name).strip_prefix(&prefix).map_or(name, Utf8Path::as_str))
+        .collect()
+}
+
+/// Writes a table that contains a row for each entry in `rows` and a row for `total`.
+pub(crate) fn write(
+    out: &mut dyn WriteColor,
+    header: &str,
+    rows: &[(&str, &Summary)],
+    total: &Summary,
+) -> io::Result<()> {
+    write_inner(out, header, rows, &[], total)
+}
+
+fn write_inner(
+    out: &mut dyn WriteColor,
+    header: &str,
+    rows: &[(&str, &Summary)],
+    empty_rows: &[(&str, &Summary)],
+    total: &Summary,
+) -> io::Result<()> {
+    let name_width = rows
+        .iter()
+        .chain(empty_rows)
+        .map(|(name, _)| name.len())
+        .chain([NAME_WIDTH, header.len()])
+        .max()
+        .unwrap();
+    let divider = "-".repeat(name_width + COLUMNS.iter().flatten().map(|&(_, w)| w).sum::<usize>());
+
+    write!(out, "{header:<name_width$}")?;
+    for &(column, width) in COLUMNS.iter().flatten() {
+        write!(out, "{column:>width$}")?;
+    }
+    writeln!(out)?;
+    writeln!(out, "{divider}")?;
+    for &(name, summary) in rows {
+        write_row(out, name_width, name, summary)?;
+    }
+    if !empty_rows.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "Files which contain no functions:")?;
+        for &(name, summary) in empty_rows {
+            write_row(out, name_width, name, summary)?;
+        }
+    }
+    writeln!(out, "{divider}")?;
+    write_row(out, name_width, "TOTAL", total)?;
+    out.flush()
+}
+
+fn write_row(
+    out: &mut dyn WriteColor,
+    name_width: usize,
+    name: &str,
+    summary: &Summary,
+) -> io::Result<()> {
+    write!(out, "{name:<name_width$}")?;
+    let counts = [&summary.regions, &summary.functions, &summary.lines, &summary.branches];
+    for (counts, columns) in counts.into_iter().zip(COLUMNS) {
+        let color = color(counts);
+        write!(out, "{:>width$}", counts.count, width = columns[0].1)?;
+        write_colored(
+            out,
+            color,
+            format_args!(
+                "{:>width$}",
+                counts.count.saturating_sub(counts.covered),
+                width = columns[1].1
+            ),
+        )?;
+        if counts.count == 0 {
+            write!(out, "{:>width$}", "-", width = columns[2].1)?;
+        } else {
+            write_colored(
+                out,
+                color,
+                format_args!("{:>width$.2}%", counts.percent, width = columns[2].1 - 1),
+            )?;
+        }
+    }
+    writeln!(out)
+}
+
+fn write_colored(
+    out: &mut dyn WriteColor,
+    color: Color,
+    args: fmt::Arguments<'_>,
+) -> io::Result<()> {
+    out.set_color(ColorSpec::new().set_fg(Some(color)))?;
+    out.write_fmt(args)?;
+    out.reset()
+}
+
+fn color(counts: &CoverageCounts) -> Color {
+    if counts.covered == counts.count {
+        Color::Green
+    } else if counts.percent >= 80_f64 {
+        Color::Yellow
+    } else {
+        Color::Red
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cargo_llvm_cov::json::LlvmCovJsonExport;
+    use termcolor::NoColor;
+
+    use crate::fs;
+
+    #[test]
+    fn write_table() {
+        let model =
+            format!("{}/tests/fixtures/coverage-reports/real1/all", env!("CARGO_MANIFEST_DIR"));
+        let json: LlvmCovJsonExport =
+            serde_json::from_str(&fs::read_to_string(format!("{model}.json")).unwrap()).unwrap();
+        let mut out = NoColor::new(vec![]);
+        super::write_files(&mut out, &json).unwrap();
+        let expected = fs::read_to_string(format!("{model}.summary.txt")).unwrap();
+        assert_eq!(str::from_utf8(out.get_ref()).unwrap().trim_end(), expected.trim_end());
+    }
+
+    #[test]
+    fn strip_redundant_prefix() {
+        assert_eq!(super::strip_redundant_prefix(vec!["/ws/src/lib.rs"]), ["/ws/src/lib.rs"]);
+        assert_eq!(super::strip_redundant_prefix(vec!["/ws/src/lib.rs", "/ws/src/a/mod.rs"]), [
+            "lib.rs", "a/mod.rs"
+        ]);
+        assert_eq!(super::strip_redundant_prefix(vec!["/ws/a/src/lib.rs", "/ws/b/src/lib.rs"]), [
+            "a/src/lib.rs",
+            "b/src/lib.rs"
+        ]);
+        assert_eq!(super::strip_redundant_prefix(vec!["src/lib.rs", "member1/src/lib.rs"]), [
+            "src/lib.rs",
+            "member1/src/lib.rs"
+        ]);
+    }
+}